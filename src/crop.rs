@@ -0,0 +1,485 @@
+use iced::advanced::layout;
+use iced::advanced::mouse;
+use iced::advanced::renderer;
+use iced::advanced::widget::tree;
+use iced::advanced::Widget;
+use iced::advanced::graphics::core;
+use iced::border::Radius;
+use iced::Background;
+use iced::Border;
+use iced::Color;
+use iced::Element;
+use iced::Event;
+use iced::Length;
+use iced::Point;
+use iced::Rectangle;
+use iced::Shadow;
+use iced::Size;
+use iced::Theme;
+use iced::Vector;
+
+use std::fmt;
+
+use crate::export::VideoInfo;
+use crate::timeline::{hex_to_rgb, hex_to_rgba};
+use crate::Config;
+
+/// how close (in pixels) to a corner of the crop a press has to be to resize it instead of moving it
+const CORNER_SIZE: f32 = 12.0;
+
+/// crops smaller than this many pixels across are taken as a click, which clears the crop
+const MIN_CROP: f32 = 16.0;
+
+/// The part of the frame that is kept on export, as fractions (from 0 to 1) of the width and height of the video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Crop {
+    /// The ffmpeg filter for it, rounded to even sizes since most pixel formats cant be cropped to odd ones
+    pub fn filter(self) -> String {
+        format!(
+            "crop=trunc(iw*{}/2)*2:trunc(ih*{}/2)*2:trunc(iw*{}):trunc(ih*{})",
+            self.width, self.height, self.x, self.y
+        )
+    }
+
+    /// What `info` looks like once its cropped
+    pub fn apply(self, info: VideoInfo) -> VideoInfo {
+        VideoInfo {
+            width: (info.width as f32 * self.width) as u32 / 2 * 2,
+            height: (info.height as f32 * self.height) as u32 / 2 * 2,
+            fps: info.fps,
+        }
+    }
+
+    /// The same part of the frame once the frame is turned a quarter clockwise
+    pub fn turned(self) -> Crop {
+        Crop {
+            x: 1.0 - self.y - self.height,
+            y: self.x,
+            width: self.height,
+            height: self.width,
+        }
+    }
+
+    /// The same part of the frame once the frame is flipped, horizontally or vertically
+    pub fn flipped(self, horizontal: bool) -> Crop {
+        if horizontal {
+            Crop { x: 1.0 - self.x - self.width, ..self }
+        } else {
+            Crop { y: 1.0 - self.y - self.height, ..self }
+        }
+    }
+
+    /// Shrinks the crop around its middle until it has the aspect ratio of `aspect`, `source` is the size of the video
+    pub fn fit(self, aspect: Aspect, source: (u32, u32)) -> Crop {
+        let Some(ratio) = aspect.ratio() else {
+            return self;
+        };
+        let (source_width, source_height) = (source.0 as f32, source.1 as f32);
+        let mut width = self.width * source_width;
+        let mut height = self.height * source_height;
+        if width / height > ratio {
+            width = height * ratio;
+        } else {
+            height = width / ratio;
+        }
+
+        let (width, height) = (width / source_width, height / source_height);
+        Crop {
+            x: self.x + (self.width - width) / 2.0,
+            y: self.y + (self.height - height) / 2.0,
+            width,
+            height,
+        }
+    }
+}
+
+/// The shapes a crop can be held to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aspect {
+    Free,
+    Widescreen,
+    Vertical,
+    Square,
+    Standard,
+}
+
+impl Aspect {
+    pub const ALL: [Aspect; 5] = [Aspect::Free, Aspect::Widescreen, Aspect::Vertical, Aspect::Square, Aspect::Standard];
+
+    /// width divided by height, `None` for free-form crops
+    fn ratio(self) -> Option<f32> {
+        match self {
+            Aspect::Free => None,
+            Aspect::Widescreen => Some(16.0 / 9.0),
+            Aspect::Vertical => Some(9.0 / 16.0),
+            Aspect::Square => Some(1.0),
+            Aspect::Standard => Some(4.0 / 3.0),
+        }
+    }
+}
+
+impl fmt::Display for Aspect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Aspect::Free => write!(f, "free"),
+            Aspect::Widescreen => write!(f, "16:9"),
+            Aspect::Vertical => write!(f, "9:16"),
+            Aspect::Square => write!(f, "1:1"),
+            Aspect::Standard => write!(f, "4:3"),
+        }
+    }
+}
+
+/// The biggest rectangle with the aspect ratio `ratio` that fits in the middle of `bounds`
+fn fit(ratio: f32, bounds: Rectangle) -> Rectangle {
+    let size = if bounds.width / bounds.height > ratio {
+        Size::new(bounds.height * ratio, bounds.height)
+    } else {
+        Size::new(bounds.width, bounds.width / ratio)
+    };
+    Rectangle::new(
+        Point::new(bounds.x + (bounds.width - size.width) / 2.0, bounds.y + (bounds.height - size.height) / 2.0),
+        size,
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    /// a new crop (or a corner of the old one) being pulled out from `anchor`, in pixels
+    Draw { anchor: Point },
+    /// the crop being moved, `grab` is where it was grabbed relative to its corner
+    Move { grab: Vector },
+}
+
+#[derive(Default)]
+struct State {
+    drag: Option<Drag>,
+}
+
+/// Sits on top of the video player, dragging over it picks what part of the frame is kept
+pub struct CropArea<Message> {
+    pub crop: Option<Crop>,
+    pub aspect: Aspect,
+    /// the size of the video, the preview is letterboxed so it cant be read from the frames
+    pub source: (u32, u32),
+    /// the size of the frames the player shows
+    pub frame: (i32, i32),
+    pub config: Config,
+    pub on_change: Box<dyn Fn(Option<Crop>) -> Message>,
+}
+
+impl<Message> CropArea<Message> {
+    /// Where the video itself ends up inside `bounds`, first the player fits the frame into it, then the frame has the video in its middle
+    fn video_bounds(&self, bounds: Rectangle) -> Rectangle {
+        let frame = fit(self.frame.0 as f32 / self.frame.1.max(1) as f32, bounds);
+        fit(self.source.0 as f32 / self.source.1.max(1) as f32, frame)
+    }
+
+    fn crop_bounds(&self, video: Rectangle) -> Option<Rectangle> {
+        self.crop.map(|crop| Rectangle {
+            x: video.x + crop.x * video.width,
+            y: video.y + crop.y * video.height,
+            width: crop.width * video.width,
+            height: crop.height * video.height,
+        })
+    }
+
+    fn to_crop(&self, video: Rectangle, bounds: Rectangle) -> Crop {
+        Crop {
+            x: (bounds.x - video.x) / video.width,
+            y: (bounds.y - video.y) / video.height,
+            width: bounds.width / video.width,
+            height: bounds.height / video.height,
+        }
+    }
+
+    /// The corner of the crop that is under `position`
+    fn corner(&self, crop: Rectangle, position: Point) -> Option<Point> {
+        [
+            crop.position(),
+            Point::new(crop.x + crop.width, crop.y),
+            Point::new(crop.x, crop.y + crop.height),
+            Point::new(crop.x + crop.width, crop.y + crop.height),
+        ]
+        .into_iter()
+        .find(|corner| corner.distance(position) <= CORNER_SIZE)
+    }
+
+    /// The crop pulled from `anchor` to `position`, held to the aspect ratio and kept inside the video
+    fn draw_from(&self, video: Rectangle, anchor: Point, position: Point) -> Rectangle {
+        let dx = position.x - anchor.x;
+        let dy = position.y - anchor.y;
+        // how far the crop can go in the direction its pulled before leaving the video
+        let room_x = if dx >= 0.0 { video.x + video.width - anchor.x } else { anchor.x - video.x };
+        let room_y = if dy >= 0.0 { video.y + video.height - anchor.y } else { anchor.y - video.y };
+
+        // pixels of the video on screen are the same shape as pixels of the source, so the ratio holds here too
+        let (width, height) = match self.aspect.ratio() {
+            Some(ratio) => {
+                let width = dx.abs().max(dy.abs() * ratio).min(room_x).min(room_y * ratio);
+                (width, width / ratio)
+            }
+            None => (dx.abs().min(room_x), dy.abs().min(room_y)),
+        };
+        Rectangle {
+            x: if dx >= 0.0 { anchor.x } else { anchor.x - width },
+            y: if dy >= 0.0 { anchor.y } else { anchor.y - height },
+            width,
+            height,
+        }
+    }
+}
+
+impl<Message, Theme, Renderer: iced::advanced::Renderer> Widget<Message, Theme, Renderer> for CropArea<Message> {
+    fn draw(
+            &self,
+            tree: &iced::advanced::widget::Tree,
+            renderer: &mut Renderer,
+            theme: &Theme,
+            style: &iced::advanced::renderer::Style,
+            layout: iced::advanced::Layout<'_>,
+            cursor: iced::advanced::mouse::Cursor,
+            viewport: &iced::Rectangle,
+        ) {
+        let video = self.video_bounds(layout.bounds());
+        let outline = |renderer: &mut Renderer, bounds: Rectangle, color: Color| {
+            renderer.fill_quad(renderer::Quad {
+                bounds,
+                border: Border {
+                    color,
+                    width: 2.0,
+                    radius: Radius::new(0.0),
+                },
+                shadow: Shadow::default(),
+            }, Background::Color(Color::TRANSPARENT));
+        };
+
+        let Some(crop) = self.crop_bounds(video) else {
+            // nothing is cropped yet, so just show where a crop can be drawn
+            outline(renderer, video, hex_to_rgba(&self.config.main_color, 0.5));
+            return;
+        };
+
+        // everything that gets cut away is darkened
+        let shade = Color::from_rgba(0.0, 0.0, 0.0, 0.6);
+        for bounds in [
+            Rectangle { x: video.x, y: video.y, width: video.width, height: crop.y - video.y },
+            Rectangle { x: video.x, y: crop.y + crop.height, width: video.width, height: video.y + video.height - crop.y - crop.height },
+            Rectangle { x: video.x, y: crop.y, width: crop.x - video.x, height: crop.height },
+            Rectangle { x: crop.x + crop.width, y: crop.y, width: video.x + video.width - crop.x - crop.width, height: crop.height },
+        ] {
+            if bounds.width > 0.0 && bounds.height > 0.0 {
+                renderer.fill_quad(renderer::Quad {
+                    bounds,
+                    border: Border::default(),
+                    shadow: Shadow::default(),
+                }, Background::Color(shade));
+            }
+        }
+
+        outline(renderer, crop, hex_to_rgb(&self.config.main_color));
+        for corner in [
+            crop.position(),
+            Point::new(crop.x + crop.width, crop.y),
+            Point::new(crop.x, crop.y + crop.height),
+            Point::new(crop.x + crop.width, crop.y + crop.height),
+        ] {
+            renderer.fill_quad(renderer::Quad {
+                bounds: Rectangle::new(corner - Vector::new(4.0, 4.0), Size::new(8.0, 8.0)),
+                border: Border::default().rounded(2.0),
+                shadow: Shadow::default(),
+            }, Background::Color(hex_to_rgb(&self.config.main_color)));
+        }
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> iced::Size<Length> {
+        iced::Size { width: Length::Fill, height: Length::Fill }
+    }
+
+    fn layout(
+            &self,
+            tree: &mut iced::advanced::widget::Tree,
+            renderer: &Renderer,
+            limits: &iced::advanced::layout::Limits,
+        ) -> iced::advanced::layout::Node {
+        layout::Node::new(limits.max())
+    }
+
+    fn mouse_interaction(
+            &self,
+            tree: &iced::advanced::widget::Tree,
+            layout: layout::Layout<'_>,
+            cursor: iced::advanced::mouse::Cursor,
+            viewport: &Rectangle,
+            _renderer: &Renderer,
+        ) -> iced::advanced::mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+        match state.drag {
+            Some(Drag::Draw { .. }) => return mouse::Interaction::Crosshair,
+            Some(Drag::Move { .. }) => return mouse::Interaction::Grabbing,
+            None => {}
+        }
+
+        let video = self.video_bounds(layout.bounds());
+        let Some(position) = cursor.position_over(video) else {
+            return mouse::Interaction::None;
+        };
+        if let Some(crop) = self.crop_bounds(video) {
+            if self.corner(crop, position).is_some() {
+                return mouse::Interaction::Crosshair;
+            }
+            if crop.contains(position) {
+                return mouse::Interaction::Grab;
+            }
+        }
+        mouse::Interaction::Crosshair
+    }
+
+    fn on_event(
+            &mut self,
+            tree: &mut iced::advanced::widget::Tree,
+            event: iced::Event,
+            layout: layout::Layout<'_>,
+            cursor: iced::advanced::mouse::Cursor,
+            _renderer: &Renderer,
+            _clipboard: &mut dyn iced::advanced::Clipboard,
+            shell: &mut iced::advanced::Shell<'_, Message>,
+            _viewport: &Rectangle,
+        ) -> iced::advanced::graphics::core::event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        let video = self.video_bounds(layout.bounds());
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(position) = cursor.position_over(video) else {
+                    return core::event::Status::Ignored;
+                };
+                state.drag = Some(match self.crop_bounds(video) {
+                    // grabbing a corner pulls it from the corner across from it
+                    Some(crop) if self.corner(crop, position).is_some() => {
+                        let corner = self.corner(crop, position).unwrap();
+                        Drag::Draw {
+                            anchor: Point::new(
+                                if corner.x == crop.x { crop.x + crop.width } else { crop.x },
+                                if corner.y == crop.y { crop.y + crop.height } else { crop.y },
+                            ),
+                        }
+                    }
+                    Some(crop) if crop.contains(position) => Drag::Move { grab: position - crop.position() },
+                    _ => {
+                        // the old crop is gone as soon as a new one is started
+                        shell.publish((self.on_change)(Some(self.to_crop(video, Rectangle::new(position, Size::ZERO)))));
+                        Drag::Draw { anchor: position }
+                    }
+                });
+                core::event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                match state.drag {
+                    Some(Drag::Draw { anchor }) => {
+                        let bounds = self.draw_from(video, anchor, position);
+                        shell.publish((self.on_change)(Some(self.to_crop(video, bounds))));
+                    }
+                    Some(Drag::Move { grab }) => {
+                        let Some(crop) = self.crop_bounds(video) else {
+                            return core::event::Status::Ignored;
+                        };
+                        let corner = Point::new(
+                            (position.x - grab.x).clamp(video.x, video.x + video.width - crop.width),
+                            (position.y - grab.y).clamp(video.y, video.y + video.height - crop.height),
+                        );
+                        shell.publish((self.on_change)(Some(self.to_crop(video, Rectangle::new(corner, crop.size())))));
+                    }
+                    None => return core::event::Status::Ignored,
+                }
+                core::event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let Some(drag) = state.drag.take() else {
+                    return core::event::Status::Ignored;
+                };
+                // a click without dragging (or a crop too small to see) clears it
+                let too_small = self.crop_bounds(video).map_or(true, |crop| crop.width < MIN_CROP || crop.height < MIN_CROP);
+                if matches!(drag, Drag::Draw { .. }) && too_small {
+                    shell.publish((self.on_change)(None));
+                }
+                core::event::Status::Captured
+            }
+            _ => core::event::Status::Ignored
+        }
+    }
+}
+
+impl<'a, Message> From<CropArea<Message>> for Element<'a, Message, Theme>
+where
+    Message: Clone + 'a,
+{
+    fn from(crop_area: CropArea<Message>) -> Self {
+        Self::new(crop_area)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where a point of the frame (as fractions of it) ends up after `quarter_turns` clockwise and then a horizontal
+    /// flip if `mirrored`, the same as the transpose and hflip filters the export runs
+    fn transform_point((x, y): (f32, f32), quarter_turns: u8, mirrored: bool) -> (f32, f32) {
+        let (mut x, mut y) = (x, y);
+        for _ in 0..quarter_turns {
+            (x, y) = (1.0 - y, x);
+        }
+        if mirrored {
+            x = 1.0 - x;
+        }
+        (x, y)
+    }
+
+    #[test]
+    fn turned_and_flipped_crops_follow_the_frame() {
+        let crop = Crop { x: 0.1, y: 0.2, width: 0.3, height: 0.5 };
+        for quarter_turns in 0..4 {
+            for mirrored in [false, true] {
+                let mut moved = (0..quarter_turns).fold(crop, |crop, _| crop.turned());
+                if mirrored {
+                    moved = moved.flipped(true);
+                }
+
+                // the corners of the crop land on the corners of the moved crop, in some order
+                let corners = [(crop.x, crop.y), (crop.x + crop.width, crop.y + crop.height)]
+                    .map(|corner| transform_point(corner, quarter_turns, mirrored));
+                let (left, right) = (corners[0].0.min(corners[1].0), corners[0].0.max(corners[1].0));
+                let (top, bottom) = (corners[0].1.min(corners[1].1), corners[0].1.max(corners[1].1));
+                let expected = Crop { x: left, y: top, width: right - left, height: bottom - top };
+
+                for (value, expected) in [(moved.x, expected.x), (moved.y, expected.y), (moved.width, expected.width), (moved.height, expected.height)] {
+                    assert!((value - expected).abs() < 1e-5, "{quarter_turns} turns, mirrored {mirrored}: {moved:?} isnt {expected:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vertical_flip() {
+        let crop = Crop { x: 0.1, y: 0.2, width: 0.3, height: 0.5 };
+        let flipped = crop.flipped(false);
+        assert!((flipped.y - 0.3).abs() < 1e-5);
+        assert_eq!((flipped.x, flipped.width, flipped.height), (crop.x, crop.width, crop.height));
+    }
+}
@@ -0,0 +1,1363 @@
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::process::{Child, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use essi_ffmpeg::FFmpeg;
+
+use crate::crop::Crop;
+use crate::string_to_static_str;
+use crate::timeline::Segment;
+
+/// How big an exported file is allowed to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetSize {
+    /// never compress, only trim
+    Unlimited,
+    /// in bytes
+    Limit(u64),
+}
+
+impl TargetSize {
+    /// the limits of the usual places clips get sent to
+    pub const PRESETS: [TargetSize; 6] = [
+        TargetSize::Limit(8_000_000),
+        TargetSize::Limit(10_000_000),
+        TargetSize::Limit(25_000_000),
+        TargetSize::Limit(50_000_000),
+        TargetSize::Limit(500_000_000),
+        TargetSize::Unlimited,
+    ];
+}
+
+impl Default for TargetSize {
+    fn default() -> Self {
+        TargetSize::Limit(10_000_000)
+    }
+}
+
+impl fmt::Display for TargetSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSize::Unlimited => write!(f, "no limit"),
+            TargetSize::Limit(bytes) if bytes % 1_000_000 == 0 => write!(f, "{} MB", bytes / 1_000_000),
+            TargetSize::Limit(bytes) => write!(f, "{:.1} MB", *bytes as f64 / 1_000_000.0),
+        }
+    }
+}
+
+impl FromStr for TargetSize {
+    type Err = String;
+
+    /// Parses sizes like `25M`, `8mb`, `500K` or plain bytes, `none` turns compression off
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lowercase = value.trim().to_lowercase();
+        if lowercase == "none" || lowercase == "unlimited" {
+            return Ok(TargetSize::Unlimited);
+        }
+
+        let size = lowercase.strip_suffix('b').unwrap_or(&lowercase);
+        let (number, multiplier) = match size.chars().last() {
+            Some('k') => (&size[..size.len() - 1], 1_000.0),
+            Some('m') => (&size[..size.len() - 1], 1_000_000.0),
+            Some('g') => (&size[..size.len() - 1], 1_000_000_000.0),
+            _ => (size, 1.0),
+        };
+
+        match number.trim().parse::<f64>() {
+            Ok(number) if number > 0.0 => Ok(TargetSize::Limit((number * multiplier) as u64)),
+            _ => Err(format!("`{value}` is not a valid size, try something like 25M or none")),
+        }
+    }
+}
+
+/// The container an export is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Webm,
+    Mkv,
+}
+
+impl Container {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+            Container::Mkv => "mkv",
+        }
+    }
+
+    /// the name ffmpeg knows the muxer by
+    fn muxer(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+            Container::Mkv => "matroska",
+        }
+    }
+
+    /// the codecs that get used when only the container is picked
+    pub fn default_codecs(self) -> (VideoCodec, AudioCodec) {
+        match self {
+            Container::Mp4 => (VideoCodec::H264, AudioCodec::Aac),
+            Container::Webm => (VideoCodec::Vp9, AudioCodec::Opus),
+            Container::Mkv => (VideoCodec::H264, AudioCodec::Opus),
+        }
+    }
+}
+
+impl FromStr for Container {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "mp4" => Ok(Container::Mp4),
+            "webm" => Ok(Container::Webm),
+            "mkv" | "matroska" => Ok(Container::Mkv),
+            _ => Err(format!("`{value}` is not a container, try mp4, webm or mkv")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    fn encoder(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1 => "libaom-av1",
+        }
+    }
+
+    /// the arguments that trade encoding speed for quality
+    fn speed_args(self) -> Vec<&'static str> {
+        match self {
+            VideoCodec::H264 => vec!["-preset", "veryslow"],
+            VideoCodec::Vp9 => vec!["-deadline", "good", "-cpu-used", "2", "-row-mt", "1"],
+            VideoCodec::Av1 => vec!["-cpu-used", "4", "-row-mt", "1"],
+        }
+    }
+
+    /// the arguments for constant quality, used when the video only gets trimmed
+    fn quality_args(self) -> Vec<&'static str> {
+        match self {
+            VideoCodec::H264 => vec!["-crf", "23"],
+            VideoCodec::Vp9 => vec!["-crf", "31", "-b:v", "0"],
+            VideoCodec::Av1 => vec!["-crf", "30", "-b:v", "0"],
+        }
+    }
+
+    /// below this many bits per pixel (of every frame) the picture falls apart, so the resolution or framerate is lowered.
+    /// the newer codecs get away with less
+    fn min_bits_per_pixel(self) -> f32 {
+        match self {
+            VideoCodec::H264 => 0.04,
+            VideoCodec::Vp9 => 0.03,
+            VideoCodec::Av1 => 0.025,
+        }
+    }
+
+    /// how much of the budget the encoder is aimed at, the rest is headroom for the container and overshooting.
+    /// libvpx overshoots the most out of these
+    fn headroom(self) -> f32 {
+        match self {
+            VideoCodec::H264 => 0.93,
+            VideoCodec::Vp9 => 0.9,
+            VideoCodec::Av1 => 0.92,
+        }
+    }
+}
+
+impl fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoCodec::H264 => write!(f, "H.264"),
+            VideoCodec::Vp9 => write!(f, "VP9"),
+            VideoCodec::Av1 => write!(f, "AV1"),
+        }
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "h264" | "h.264" | "avc" | "x264" => Ok(VideoCodec::H264),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            _ => Err(format!("`{value}` is not a video codec, try h264, vp9 or av1")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    fn args(self) -> Vec<&'static str> {
+        match self {
+            AudioCodec::Aac => vec!["-c:a", "aac", "-aac_coder", "twoloop"],
+            AudioCodec::Opus => vec!["-c:a", "libopus"],
+        }
+    }
+
+    /// the part of the total bitrate the audio gets, opus sounds fine with less
+    fn share(self) -> f32 {
+        match self {
+            AudioCodec::Aac => 0.1,
+            AudioCodec::Opus => 0.07,
+        }
+    }
+
+    /// the bitrate used when the video only gets trimmed
+    fn quality_bitrate(self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "160k",
+            AudioCodec::Opus => "128k",
+        }
+    }
+}
+
+impl fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioCodec::Aac => write!(f, "AAC"),
+            AudioCodec::Opus => write!(f, "Opus"),
+        }
+    }
+}
+
+impl FromStr for AudioCodec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "aac" => Ok(AudioCodec::Aac),
+            "opus" => Ok(AudioCodec::Opus),
+            _ => Err(format!("`{value}` is not an audio codec, try aac or opus")),
+        }
+    }
+}
+
+/// What an export gets encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub container: Container,
+    pub video: VideoCodec,
+    pub audio: AudioCodec,
+}
+
+impl Format {
+    /// the ones that can be picked in the editor
+    pub const PRESETS: [Format; 4] = [
+        Format { container: Container::Mp4, video: VideoCodec::H264, audio: AudioCodec::Aac },
+        Format { container: Container::Webm, video: VideoCodec::Vp9, audio: AudioCodec::Opus },
+        Format { container: Container::Webm, video: VideoCodec::Av1, audio: AudioCodec::Opus },
+        Format { container: Container::Mkv, video: VideoCodec::H264, audio: AudioCodec::Opus },
+    ];
+
+    /// Fails for codecs the container cant hold, webm only takes vp9/av1 and opus
+    pub fn new(container: Container, video: VideoCodec, audio: AudioCodec) -> Result<Self, String> {
+        if container == Container::Webm && (video == VideoCodec::H264 || audio == AudioCodec::Aac) {
+            return Err(format!("webm cant hold {video} or {audio}, only vp9/av1 and opus"));
+        }
+        Ok(Format { container, video, audio })
+    }
+
+    /// Encodes at constant quality, for when the video only gets trimmed
+    fn quality_args(self) -> Vec<&'static str> {
+        let mut args = vec!["-c:v", self.video.encoder()];
+        args.extend(self.video.quality_args());
+        args.extend(self.video.speed_args());
+        args.extend(self.audio.args());
+        args.extend(["-b:a", self.audio.quality_bitrate()]);
+        args.extend(["-f", self.container.muxer()]);
+        args
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::PRESETS[0]
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}", self.container.extension().to_uppercase(), self.video, self.audio)
+    }
+}
+
+/// The size and framerate of the video stream of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+}
+
+impl VideoInfo {
+    /// What the p in 720p counts, which is the width of videos filmed upright
+    pub fn short_side(self) -> u32 {
+        self.width.min(self.height)
+    }
+}
+
+/// Turns and mirrors of the frame, they happen before the crop so it is picked on what the preview shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Transform {
+    /// clockwise, the flips happen after
+    pub quarter_turns: u8,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Transform {
+    pub fn is_identity(self) -> bool {
+        self == Transform::default()
+    }
+
+    fn filters(self) -> Vec<String> {
+        let mut filters: Vec<String> = match self.quarter_turns % 4 {
+            1 => vec!["transpose=clock".to_string()],
+            2 => vec!["hflip".to_string(), "vflip".to_string()],
+            3 => vec!["transpose=cclock".to_string()],
+            _ => Vec::new(),
+        };
+        if self.flip_horizontal {
+            filters.push("hflip".to_string());
+        }
+        if self.flip_vertical {
+            filters.push("vflip".to_string());
+        }
+        filters
+    }
+
+    /// What `info` looks like once its turned, a quarter turn swaps the width and height
+    pub fn apply(self, info: VideoInfo) -> VideoInfo {
+        if self.quarter_turns % 2 == 1 {
+            VideoInfo { width: info.height, height: info.width, fps: info.fps }
+        } else {
+            info
+        }
+    }
+
+    /// The `video-direction` of gstreamers videoflip that does the same thing. A vertical flip is a half turn
+    /// and a horizontal flip, so every mix of them comes down to a turn that might be mirrored after
+    pub fn video_direction(self) -> &'static str {
+        let mut turns = self.quarter_turns % 4;
+        let mut mirrored = self.flip_horizontal;
+        if self.flip_vertical {
+            turns = (turns + 2) % 4;
+            mirrored = !mirrored;
+        }
+        match (turns, mirrored) {
+            (0, false) => "identity",
+            (1, false) => "90r",
+            (2, false) => "180",
+            (3, false) => "90l",
+            (0, true) => "horiz",
+            // a quarter turn and then a mirror is flipping over a diagonal
+            (1, true) => "ul-lr",
+            (2, true) => "vert",
+            _ => "ur-ll",
+        }
+    }
+}
+
+/// The height and framerate a re-encoded export ends up at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Picture {
+    /// the shorter side, so the width of upright videos, see [`VideoInfo::short_side`]
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl Picture {
+    /// The filters that get `info` down to this picture, nothing is ever scaled up
+    fn filters(self, info: VideoInfo) -> Vec<String> {
+        let mut filters = Vec::new();
+        if self.height < info.short_side() {
+            if info.width < info.height {
+                filters.push(format!("scale={}:-2:flags=lanczos", self.height));
+            } else {
+                filters.push(format!("scale=-2:{}:flags=lanczos", self.height));
+            }
+        }
+        if (self.fps as f32) < info.fps.round() {
+            filters.push(format!("fps={}", self.fps));
+        }
+        filters
+    }
+}
+
+/// the steps the resolution and framerate go down when there arent enough bits for them
+pub const HEIGHTS: [u32; 3] = [1080, 720, 480];
+pub const FRAMERATES: [u32; 3] = [60, 30, 24];
+
+/// What an export of `duration` seconds gets encoded at. `height` and `fps` override what gets picked,
+/// the rest starts at the source and goes down until every pixel gets enough of the bitrate the target leaves
+pub fn picture(info: VideoInfo, format: Format, target: TargetSize, duration: f32, height: Option<u32>, fps: Option<u32>) -> Picture {
+    let mut picture = Picture {
+        height: height.unwrap_or(info.short_side()),
+        fps: fps.unwrap_or(info.fps.round() as u32),
+    };
+    let TargetSize::Limit(target) = target else {
+        return picture;
+    };
+
+    let (video_bitrate, _) = split_bitrate((target as f32 * 8.0 / 1000.0) / duration * format.video.headroom(), format.audio);
+    let bits_per_pixel = |picture: Picture| {
+        let scale = picture.height as f32 / info.short_side() as f32;
+        let pixels = info.width as f32 * info.height as f32 * scale * scale;
+        video_bitrate * 1000.0 / (pixels * picture.fps as f32)
+    };
+    let lower = |steps: &[u32], value: u32| steps.iter().copied().find(|step| *step < value);
+
+    while bits_per_pixel(picture) < format.video.min_bits_per_pixel() {
+        // anything above 30fps is the cheapest thing to give up, then the resolution, then the last few frames
+        if let Some(lower_fps) = lower(&FRAMERATES, picture.fps).filter(|_| fps.is_none() && picture.fps > 30) {
+            picture.fps = lower_fps;
+        } else if let Some(lower_height) = lower(&HEIGHTS, picture.height).filter(|_| height.is_none()) {
+            picture.height = lower_height;
+        } else if let Some(lower_fps) = lower(&FRAMERATES, picture.fps).filter(|_| fps.is_none()) {
+            picture.fps = lower_fps;
+        } else {
+            break;
+        }
+    }
+    picture
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationKind {
+    Gif,
+    Webp,
+}
+
+/// How gifs spread the colors that arent in their palette, these are the ones paletteuse knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    Bayer,
+    Heckbert,
+    FloydSteinberg,
+    Sierra2,
+    Sierra2_4a,
+}
+
+impl Dither {
+    fn name(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Bayer => "bayer",
+            Dither::Heckbert => "heckbert",
+            Dither::FloydSteinberg => "floyd_steinberg",
+            Dither::Sierra2 => "sierra2",
+            Dither::Sierra2_4a => "sierra2_4a",
+        }
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        [Dither::None, Dither::Bayer, Dither::Heckbert, Dither::FloydSteinberg, Dither::Sierra2, Dither::Sierra2_4a]
+            .into_iter()
+            .find(|dither| dither.name() == value.trim().to_lowercase())
+            .ok_or_else(|| format!("`{value}` is not a dither, try none, bayer, heckbert, floyd_steinberg, sierra2 or sierra2_4a"))
+    }
+}
+
+/// A gif or animated webp, `fps` and `width` are where it starts out, they get lowered until it fits the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Animation {
+    pub kind: AnimationKind,
+    pub fps: u32,
+    pub width: u32,
+    /// only used by gifs
+    pub dither: Dither,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            kind: AnimationKind::Gif,
+            fps: 15,
+            width: 480,
+            dither: Dither::Sierra2_4a,
+        }
+    }
+}
+
+/// the smallest an animation gets shrunk down to when it doesnt fit in the target
+const MIN_ANIMATION_FPS: u32 = 5;
+const MIN_ANIMATION_WIDTH: u32 = 120;
+
+/// The formats the audio of the selection can be extracted as, flac and wav are lossless so they ignore the bitrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Opus,
+    Mp3,
+    Flac,
+    Wav,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 4] = [AudioFormat::Opus, AudioFormat::Mp3, AudioFormat::Flac, AudioFormat::Wav];
+
+    fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "opus",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "libopus",
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "pcm_s16le",
+        }
+    }
+
+    fn lossless(self) -> bool {
+        matches!(self, AudioFormat::Flac | AudioFormat::Wav)
+    }
+
+    /// the lowest bitrate (in kbit/s) the encoder takes, lame refuses to go below 8
+    fn min_bitrate(self) -> u32 {
+        match self {
+            AudioFormat::Opus => 6,
+            _ => 8,
+        }
+    }
+}
+
+impl FromStr for AudioFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        AudioFormat::ALL
+            .into_iter()
+            .find(|format| format.extension() == value.trim().to_lowercase())
+            .ok_or_else(|| format!("`{value}` is not an audio format, try opus, mp3, flac or wav"))
+    }
+}
+
+/// Only the audio of the selection, `bitrate` (in kbit/s) gets lowered if it wouldnt fit the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioOnly {
+    pub format: AudioFormat,
+    pub bitrate: u32,
+}
+
+impl Default for AudioOnly {
+    fn default() -> Self {
+        AudioOnly {
+            format: AudioFormat::Opus,
+            bitrate: 128,
+        }
+    }
+}
+
+/// What kind of file an export is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Video(Format),
+    Animation(Animation),
+    Audio(AudioOnly),
+}
+
+impl Output {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Output::Video(format) => format.container.extension(),
+            Output::Animation(Animation { kind: AnimationKind::Gif, .. }) => "gif",
+            Output::Animation(Animation { kind: AnimationKind::Webp, .. }) => "webp",
+            Output::Audio(audio) => audio.format.extension(),
+        }
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::Video(Format::default())
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Video(format) => write!(f, "{format}"),
+            Output::Animation(animation) => write!(
+                f,
+                "{} {}fps {}px",
+                if animation.kind == AnimationKind::Gif { "GIF" } else { "WebP" },
+                animation.fps,
+                animation.width
+            ),
+            Output::Audio(audio) if audio.format.lossless() => write!(f, "{} audio", audio.format.extension().to_uppercase()),
+            Output::Audio(audio) => write!(f, "{} audio {}k", audio.format.extension().to_uppercase(), audio.bitrate),
+        }
+    }
+}
+
+/// overwrites without asking, and writes machine readable progress to stdout instead of the stats line
+const PROGRESS_ARGS: [&str; 4] = ["-y", "-progress", "pipe:1", "-nostats"];
+
+/// How far along an export is
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// the ffmpeg run that is going, starting at 1
+    pub pass: u32,
+    /// how many runs the export needs, retries add more of them
+    pub passes: u32,
+    /// how much of the current run is done, from 0 to 1
+    pub fraction: f32,
+    /// how long the whole export should still take, guessed from the speed of the current run
+    pub eta: Option<Duration>,
+}
+
+/// Why an export didnt end up with a file
+#[derive(Debug, Clone)]
+pub enum ExportError {
+    /// the cancel flag got set while it was running
+    Cancelled,
+    /// ffmpeg couldnt be started or failed, with what went wrong
+    Failed(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Cancelled => write!(f, "the export was cancelled"),
+            ExportError::Failed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+/// The size of a file that ffmpeg should have written
+fn file_size(file: &PathBuf) -> Result<u64, ExportError> {
+    file.metadata()
+        .map(|metadata| metadata.size())
+        .map_err(|error| ExportError::Failed(format!("couldnt read {}: {error}", file.display())))
+}
+
+/// Runs the ffmpeg processes of an export and turns their progress output into [`Progress`]
+struct Reporter<'a> {
+    pass: u32,
+    passes: u32,
+    /// the length of the output in seconds
+    duration: f32,
+    /// the file being exported to, removed when the export gets cancelled
+    file: &'a PathBuf,
+    /// what the temporary files of this export start with, see [`temp_prefix`]
+    temp: PathBuf,
+    cancel: &'a AtomicBool,
+    on_progress: &'a mut dyn FnMut(Progress),
+}
+
+impl Reporter<'_> {
+    /// Waits for an ffmpeg process started with [`PROGRESS_ARGS`] and a piped stdout.
+    /// Fails if the export got cancelled or ffmpeg did, in which case the process is killed and its leftovers removed
+    fn run(&mut self, child: std::io::Result<Child>) -> Result<(), ExportError> {
+        let mut child = child.map_err(|error| ExportError::Failed(format!("couldnt start ffmpeg: {error}")))?;
+        self.pass += 1;
+        let started = Instant::now();
+
+        let stdout = child.stdout.take().expect("ffmpeg stdout should be piped");
+        for line in BufReader::new(stdout).lines() {
+            // progress gets written every half a second, so this is checked often enough
+            if self.cancel.load(Ordering::Relaxed) {
+                let _ = child.kill();
+                break;
+            }
+
+            let Ok(line) = line else { break };
+            // is N/A until the first frame is out
+            let Some(Ok(time)) = line.strip_prefix("out_time_us=").map(str::parse::<f32>) else { continue };
+
+            let fraction = (time / 1_000_000.0 / self.duration).clamp(0.0, 1.0);
+            let eta = (fraction > 0.01).then(|| {
+                let pass_time = started.elapsed().as_secs_f32() / fraction;
+                let remaining = self.passes.saturating_sub(self.pass) as f32;
+                Duration::from_secs_f32(pass_time * (1.0 - fraction) + pass_time * remaining)
+            });
+
+            (self.on_progress)(Progress {
+                pass: self.pass,
+                passes: self.passes,
+                fraction,
+                eta,
+            });
+        }
+
+        let status = child.wait();
+        if self.cancel.load(Ordering::Relaxed) {
+            let _ = std::fs::remove_file(self.file);
+            remove_pass_logs(&self.temp);
+            return Err(ExportError::Cancelled);
+        }
+        // the output might be left over from before (or half written), so it cant be taken as the result
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => {
+                let _ = std::fs::remove_file(self.file);
+                remove_pass_logs(&self.temp);
+                Err(ExportError::Failed(format!("ffmpeg failed ({status}) during pass {}", self.pass)))
+            }
+            Err(error) => Err(ExportError::Failed(format!("couldnt wait for ffmpeg: {error}"))),
+        }
+    }
+}
+
+/// Everything that decides what an exported file looks like
+#[derive(Debug, Clone)]
+pub struct ExportSettings {
+    pub segments: Vec<Segment>,
+    pub target: TargetSize,
+    /// cut without re-encoding, which is lossless but makes every cut start at the keyframe before it
+    pub stream_copy: bool,
+    /// ignored by stream copies, they keep the codecs (and container) of the original
+    pub output: Output,
+    /// overrides for the height and framerate of video exports, they are picked from the bitrate when left out
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+    /// the part of the frame that is kept, used by everything but stream copies and audio
+    pub crop: Option<Crop>,
+    /// happens before the crop, also ignored by stream copies and audio
+    pub transform: Transform,
+}
+
+/// the lowest total bitrate (in kbit/s) a retry is allowed to go down to, below it the video is mush anyway
+const MIN_BITRATE: f32 = 100.0;
+
+/// Cuts the segments out of `old_file` and writes them one after another to `file`,
+/// compressing it if it doesnt fit in the target. Returns the size of the written file,
+/// or why there is none, which includes `cancel` getting set while it was running
+pub fn export(
+    old_file: PathBuf,
+    file: PathBuf,
+    settings: ExportSettings,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<u64, ExportError> {
+    let segments = settings.segments.clone();
+    let duration: f32 = segments.iter().map(Segment::duration).sum();
+    let target = match settings.target {
+        TargetSize::Limit(target) => target,
+        TargetSize::Unlimited => u64::MAX,
+    };
+
+    let mut reporter = Reporter {
+        pass: 0,
+        passes: 2,
+        duration,
+        file: &file,
+        temp: temp_prefix(),
+        cancel,
+        on_progress: &mut on_progress,
+    };
+
+    let format = match settings.output {
+        Output::Video(format) => format,
+        Output::Animation(animation) => return animate(&mut reporter, &old_file, &file, &segments, frame_filters(&settings), animation, target),
+        Output::Audio(audio) => return extract_audio(&mut reporter, &old_file, &file, &segments, audio, target),
+    };
+
+    // files ffmpeg cant read the size of are kept as they are, the turns and crop dont need to know it
+    let info = probe_video(&old_file)
+        .map(|info| settings.transform.apply(info))
+        .map(|info| settings.crop.map_or(info, |crop| crop.apply(info)));
+    let filters = |target| {
+        frame_filters(&settings)
+            .into_iter()
+            .chain(info.map(|info| picture(info, format, target, duration, settings.height, settings.fps).filters(info)).unwrap_or_default())
+            .collect::<Vec<String>>()
+    };
+    // gluing segments together has to know if there is audio to glue too
+    let audio = has_audio(&old_file);
+
+    if settings.stream_copy {
+        // a single segment is copied straight away, more of them are copied one by one and then glued together
+        reporter.passes = if segments.len() == 1 { 1 } else { segments.len() as u32 + 1 };
+        stream_copy(&mut reporter, &old_file, &file, &segments)?;
+
+        let size = file_size(&file)?;
+        if size <= target {
+            return Ok(size);
+        }
+        reporter.passes += 2;
+    } else if file_size(&old_file)? <= target {
+        // if old file is already smaller than the target, just trimming it should be enough
+        reporter.passes = 1;
+        reporter.run(
+            FFmpeg::new()
+                .stdout(Stdio::piped())
+                .stderr(Stdio::inherit())
+                .args(PROGRESS_ARGS)
+                .input_with_file(old_file.clone()).done()
+                .args(select(&segments, &filters(TargetSize::Unlimited), audio))
+                .args(format.quality_args())
+                .output_as_file(file.clone()).done()
+                .start()
+        )?;
+
+        let size = file_size(&file)?;
+        if size <= target {
+            return Ok(size);
+        }
+        reporter.passes += 2;
+    }
+
+    // the whole budget in kbit/s, with some headroom for the container
+    let mut bitrate = (target as f32 * 8.0 / 1000.0) / duration * format.video.headroom();
+    // the stats of the first pass dont depend on the bitrate, so retries only need to redo the second one
+    // picked once for the whole budget, the first pass stats only fit the picture they were made with
+    let filters = filters(settings.target);
+    first_pass(&mut reporter, &old_file, &segments, &filters, format, bitrate)?;
+    loop {
+        second_pass(&mut reporter, &old_file, &file, &segments, &filters, audio, format, bitrate)?;
+
+        let size = file_size(&file)?;
+        if size <= target || bitrate <= MIN_BITRATE {
+            remove_pass_logs(&reporter.temp);
+            return Ok(size);
+        }
+
+        // the encoder overshot, so scale the bitrate down by how much it overshot plus a bit extra
+        bitrate = (bitrate * (target as f32 / size as f32) * 0.97).max(MIN_BITRATE);
+        reporter.passes += 1;
+    }
+}
+
+/// Splits the total `bitrate` into the video and audio bitrates
+fn split_bitrate(bitrate: f32, audio: AudioCodec) -> (f32, f32) {
+    let audio_bitrate = bitrate * audio.share();
+    // let audio_bitrate = (( 318000.0 / ( 1.0 + std::f32::consts::E.powf(-0.0000014 * video_bitrate * 60.0) ) ) - 154000.0) / 2.0;
+    (bitrate - audio_bitrate, audio_bitrate)
+}
+
+/// Analyzes the video for the second pass, the stats are kept in the pass log file
+fn first_pass(reporter: &mut Reporter, old_file: &PathBuf, segments: &[Segment], filters: &[String], format: Format, bitrate: f32) -> Result<(), ExportError> {
+    let (video_bitrate, _) = split_bitrate(bitrate, format.audio);
+    let passlog = string_to_static_str(reporter.temp.to_str().unwrap().to_string());
+
+    reporter.run(FFmpeg::new()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .args(PROGRESS_ARGS)
+        .input_with_file(old_file.clone()).done()
+        .args(select(segments, filters, false))
+        .arg("-an")
+        .args([
+            "-c:v",
+            format.video.encoder()
+        ])
+        .args([
+            "-b:v",
+            string_to_static_str(format!("{:.0}k", video_bitrate))
+        ])
+        .args(format.video.speed_args())
+        .args([
+            "-pass",
+            "1"
+        ])
+        .args([
+            "-passlogfile",
+            passlog
+        ])
+        .args([
+            "-f",
+            "null"
+        ])
+        .output_as_file(PathBuf::from("/dev/null")).done()
+        .start()
+    )
+}
+
+/// Encodes `segments` at `bitrate` kbit/s using the stats of the first pass
+fn second_pass(reporter: &mut Reporter, old_file: &PathBuf, file: &PathBuf, segments: &[Segment], filters: &[String], audio: bool, format: Format, bitrate: f32) -> Result<(), ExportError> {
+    let (video_bitrate, audio_bitrate) = split_bitrate(bitrate, format.audio);
+    let passlog = string_to_static_str(reporter.temp.to_str().unwrap().to_string());
+
+    reporter.run(FFmpeg::new()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .args(PROGRESS_ARGS)
+        .input_with_file(old_file.clone()).done()
+        .args(select(segments, filters, audio))
+        .args(format.audio.args())
+        .args([
+            "-b:a",
+            string_to_static_str(format!("{:.0}k", audio_bitrate))
+        ])
+        .args([
+            "-c:v",
+            format.video.encoder()
+        ])
+        .args([
+            "-b:v",
+            string_to_static_str(format!("{:.0}k", video_bitrate))
+        ])
+        .args(format.video.speed_args())
+        .args([
+            "-pass",
+            "2"
+        ])
+        .args([
+            "-passlogfile",
+            passlog
+        ])
+        .args([
+            "-f",
+            format.container.muxer()
+        ])
+        .output_as_file(file.clone()).done()
+        .start()
+    )
+}
+
+/// Encodes `segments` as a gif or animated webp, lowering the fps and width until it fits in `target`
+fn animate(reporter: &mut Reporter, old_file: &PathBuf, file: &PathBuf, segments: &[Segment], frame_filters: Vec<String>, mut animation: Animation, target: u64) -> Result<u64, ExportError> {
+    reporter.passes = 1;
+    loop {
+        reporter.run(FFmpeg::new()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .args(PROGRESS_ARGS)
+            .input_with_file(old_file.clone()).done()
+            .args(animation_args(segments, &frame_filters, animation))
+            .output_as_file(file.clone()).done()
+            .start()
+        )?;
+
+        let size = file_size(&file)?;
+        if size <= target || (animation.fps <= MIN_ANIMATION_FPS && animation.width <= MIN_ANIMATION_WIDTH) {
+            return Ok(size);
+        }
+
+        // the size grows about linearly with the fps and with the square of the width, so the shrinking is split evenly between the three
+        let factor = (target as f32 / size as f32 * 0.95).cbrt();
+        animation.fps = ((animation.fps as f32 * factor) as u32).max(MIN_ANIMATION_FPS);
+        animation.width = ((animation.width as f32 * factor) as u32 / 2 * 2).max(MIN_ANIMATION_WIDTH);
+        reporter.passes += 1;
+    }
+}
+
+/// The arguments that turn `segments` into an animation, gifs get a palette made for them instead of the generic one
+fn animation_args(segments: &[Segment], frame_filters: &[String], animation: Animation) -> Vec<&'static str> {
+    let mut args = Vec::new();
+    let mut filter = String::new();
+    let mut option = "-vf";
+    if let [segment] = segments {
+        args.extend([
+            "-ss",
+            string_to_static_str(segment.start.to_string()),
+            "-t",
+            string_to_static_str(segment.duration().to_string()),
+        ]);
+    } else {
+        filter = format!("{};[joined]", concat(segments, true, false));
+        option = "-filter_complex";
+    }
+    for frame_filter in frame_filters {
+        filter.push_str(frame_filter);
+        filter.push(',');
+    }
+    // never scaled up, only down
+    filter.push_str(&format!("fps={},scale='min({},iw)':-2:flags=lanczos", animation.fps, animation.width));
+
+    match animation.kind {
+        AnimationKind::Gif => {
+            filter.push_str(&format!(
+                ",split[frames][palette_frames];[palette_frames]palettegen=stats_mode=diff[palette];[frames][palette]paletteuse=dither={}",
+                animation.dither.name()
+            ));
+            args.extend(["-an", option, string_to_static_str(filter), "-loop", "0", "-f", "gif"]);
+        }
+        AnimationKind::Webp => {
+            args.extend(["-an", option, string_to_static_str(filter), "-loop", "0"]);
+            args.extend(["-c:v", "libwebp_anim", "-lossless", "0", "-quality", "75", "-compression_level", "6", "-f", "webp"]);
+        }
+    }
+    args
+}
+
+/// The turns, flips and crop of `settings`, in the order they have to happen in
+fn frame_filters(settings: &ExportSettings) -> Vec<String> {
+    let mut filters = settings.transform.filters();
+    filters.extend(settings.crop.map(Crop::filter));
+    filters
+}
+
+/// Writes only the audio of `segments`, lossy formats get their bitrate lowered until it fits in `target`
+fn extract_audio(reporter: &mut Reporter, old_file: &PathBuf, file: &PathBuf, segments: &[Segment], audio: AudioOnly, target: u64) -> Result<u64, ExportError> {
+    // ffmpeg would fail on the -map, this says why
+    if !has_audio(old_file) {
+        return Err(ExportError::Failed("there is no audio track".to_string()));
+    }
+    reporter.passes = 1;
+    // the budget in kbit/s, with a bit of headroom for the container
+    let budget = (target as f32 * 8.0 / 1000.0) / reporter.duration * 0.97;
+    let mut bitrate = (audio.bitrate as f32).min(budget).max(audio.format.min_bitrate() as f32);
+    loop {
+        let mut args = vec!["-vn", "-c:a", audio.format.encoder()];
+        if !audio.format.lossless() {
+            args.extend(["-b:a", string_to_static_str(format!("{:.0}k", bitrate))]);
+        }
+        args.extend(["-f", audio.format.extension()]);
+
+        reporter.run(FFmpeg::new()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .args(PROGRESS_ARGS)
+            .input_with_file(old_file.clone()).done()
+            .args(select_audio(segments))
+            .args(args)
+            .output_as_file(file.clone()).done()
+            .start()
+        )?;
+
+        let size = file_size(&file)?;
+        if size <= target || audio.format.lossless() || bitrate <= audio.format.min_bitrate() as f32 {
+            return Ok(size);
+        }
+
+        bitrate = (bitrate * (target as f32 / size as f32) * 0.97).max(audio.format.min_bitrate() as f32);
+        reporter.passes += 1;
+    }
+}
+
+/// Cuts `segments` out without re-encoding anything, every cut starts at the keyframe before it
+fn stream_copy(reporter: &mut Reporter, old_file: &PathBuf, file: &PathBuf, segments: &[Segment]) -> Result<(), ExportError> {
+    if let [segment] = segments {
+        return reporter.run(copy_segment(old_file, file, segment));
+    }
+
+    let extension = file.extension().and_then(|extension| extension.to_str()).unwrap_or("mkv");
+    let parts: Vec<PathBuf> = (0..segments.len())
+        .map(|index| PathBuf::from(format!("{}-part{}.{}", reporter.temp.display(), index, extension)))
+        .collect();
+    let list = PathBuf::from(format!("{}-parts.txt", reporter.temp.display()));
+
+    let result = (|| -> Result<(), ExportError> {
+        let duration = reporter.duration;
+        for (segment, part) in segments.iter().zip(&parts) {
+            reporter.duration = segment.duration();
+            reporter.run(copy_segment(old_file, part, segment))?;
+        }
+        reporter.duration = duration;
+
+        let contents: String = parts
+            .iter()
+            .map(|part| format!("file '{}'\n", part.display().to_string().replace('\'', "'\\''")))
+            .collect();
+        std::fs::write(&list, contents).map_err(|error| ExportError::Failed(format!("couldnt write the list of parts: {error}")))?;
+
+        reporter.run(FFmpeg::new()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .args(PROGRESS_ARGS)
+            .args([
+                "-f",
+                "concat",
+                "-safe",
+                "0"
+            ])
+            .input_with_file(list.clone()).done()
+            .args([
+                "-c",
+                "copy"
+            ])
+            .output_as_file(file.clone()).done()
+            .start()
+        )
+    })();
+
+    for part in &parts {
+        let _ = std::fs::remove_file(part);
+    }
+    let _ = std::fs::remove_file(&list);
+    result
+}
+
+/// Starts copying a single `segment` into `file`, the seek happens on the input so it lands on a keyframe
+fn copy_segment(old_file: &PathBuf, file: &PathBuf, segment: &Segment) -> std::io::Result<Child> {
+    FFmpeg::new()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .args(PROGRESS_ARGS)
+        .args([
+            "-ss",
+            string_to_static_str(segment.start.to_string())
+        ])
+        .input_with_file(old_file.clone()).done()
+        .args([
+            "-t",
+            string_to_static_str(segment.duration().to_string())
+        ])
+        .args([
+            "-c",
+            "copy"
+        ])
+        .args([
+            "-avoid_negative_ts",
+            "make_zero"
+        ])
+        .output_as_file(file.clone()).done()
+        .start()
+}
+
+/// how many exports this process has started, so every one of them gets its own temporary files
+static EXPORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Where an export keeps its temporary files (the stats between the two passes and the parts of stream copies),
+/// so they dont end up in the working directory. A cancelled export can still be cleaning up while the next one
+/// runs, which is why they arent shared
+fn temp_prefix() -> PathBuf {
+    std::env::temp_dir().join(format!("sickle-{}-{}", std::process::id(), EXPORTS.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Removes what ffmpeg (x264) leaves behind in the pass log file
+fn remove_pass_logs(passlog: &PathBuf) {
+    for suffix in ["-0.log", "-0.log.mbtree", "-0.log.temp", "-0.log.mbtree.temp"] {
+        let _ = std::fs::remove_file(format!("{}{}", passlog.display(), suffix));
+    }
+}
+
+/// Formats a size in bytes the same way the target sizes are shown
+pub fn format_size(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+/// The arguments that only keep `segments` of the input, with `filters` run on the video after.
+/// A single segment is cut by seeking, more than one is cut with [`concat`], which also keeps the audio if the input has any
+fn select(segments: &[Segment], filters: &[String], audio: bool) -> Vec<&'static str> {
+    if let [segment] = segments {
+        let mut args = vec![
+            "-ss",
+            string_to_static_str(segment.start.to_string()),
+            "-t",
+            string_to_static_str(segment.duration().to_string()),
+        ];
+        if !filters.is_empty() {
+            args.extend(["-vf", string_to_static_str(filters.join(","))]);
+        }
+        return args;
+    }
+
+    let mut graph = concat(segments, true, audio);
+    let video = if filters.is_empty() {
+        "[joined]"
+    } else {
+        graph.push_str(&format!(";[joined]{}[video]", filters.join(",")));
+        "[video]"
+    };
+    let mut args = vec!["-filter_complex", string_to_static_str(graph), "-map", video];
+    if audio {
+        args.extend(["-map", "[audio]"]);
+    }
+    args
+}
+
+/// Same as [`select`] but for outputs without video, which would choke on the video filter
+fn select_audio(segments: &[Segment]) -> Vec<&'static str> {
+    if let [_] = segments {
+        let mut args = select(segments, &[], true);
+        args.extend(["-map", "0:a:0"]);
+        return args;
+    }
+    vec![
+        "-filter_complex",
+        string_to_static_str(concat(segments, false, true)),
+        "-map",
+        "[audio]",
+    ]
+}
+
+/// A filter graph that cuts every one of `segments` out of the first video and/or audio stream and glues them together,
+/// coming out as `[joined]` and `[audio]`. Every part starts its timestamps at zero again, so videos with a variable
+/// framerate dont drift away from their audio
+fn concat(segments: &[Segment], video: bool, audio: bool) -> String {
+    let mut graph = String::new();
+    let mut parts = String::new();
+    for (index, segment) in segments.iter().enumerate() {
+        if video {
+            graph.push_str(&format!("[0:v:0]trim=start={}:end={},setpts=PTS-STARTPTS[v{index}];", segment.start, segment.end));
+            parts.push_str(&format!("[v{index}]"));
+        }
+        if audio {
+            graph.push_str(&format!("[0:a:0]atrim=start={}:end={},asetpts=PTS-STARTPTS[a{index}];", segment.start, segment.end));
+            parts.push_str(&format!("[a{index}]"));
+        }
+    }
+    let outputs = match (video, audio) {
+        (true, true) => "[joined][audio]",
+        (true, false) => "[joined]",
+        _ => "[audio]",
+    };
+    format!("{graph}{parts}concat=n={}:v={}:a={}{outputs}", segments.len(), video as u8, audio as u8)
+}
+
+/// Reads the timestamps (in seconds) of every keyframe of the video, only the keyframes get decoded so this is fairly quick
+pub fn keyframes(file: &PathBuf) -> Vec<f32> {
+    let Some(output) = FFmpeg::new()
+        .stderr(Stdio::piped())
+        .args([
+            "-skip_frame",
+            "nokey"
+        ])
+        .input_with_file(file.clone()).done()
+        .args([
+            "-map",
+            "0:v:0",
+            "-vf",
+            "showinfo",
+            "-f",
+            "null"
+        ])
+        .output_as_file(PathBuf::from("/dev/null")).done()
+        .start().ok()
+        .and_then(|child| child.wait_with_output().ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut keyframes: Vec<f32> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| line.split("pts_time:").nth(1)?.split_whitespace().next()?.parse::<f32>().ok())
+        .collect();
+    keyframes.sort_by(f32::total_cmp);
+    keyframes
+}
+
+/// The keyframe a stream copy of a cut at `time` would actually start at
+pub fn keyframe_before(keyframes: &[f32], time: f32) -> f32 {
+    keyframes.iter().copied().filter(|keyframe| *keyframe <= time).fold(0.0, f32::max)
+}
+
+/// What ffmpeg prints about `file` when its given as an input
+fn probe(file: &PathBuf) -> Option<String> {
+    let output = FFmpeg::new()
+        .stderr(Stdio::piped())
+        .input_with_file(file.clone()).done()
+        .start().ok()?
+        .wait_with_output().ok()?;
+    Some(String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// Reads the size and framerate of the first video stream, from a line like
+/// `Stream #0:0: Video: h264 (High), yuv420p(progressive), 1920x1080 [SAR 1:1 DAR 16:9], 60 fps, 60 tbr`
+pub fn probe_video(file: &PathBuf) -> Option<VideoInfo> {
+    let info = probe(file)?;
+    let stream = info.lines().find(|line| line.contains("Stream #") && line.contains("Video:"))?;
+    let words: Vec<&str> = stream.split_whitespace().map(|word| word.trim_end_matches(',')).collect();
+
+    // hex ids like 0x31637661 look like a size too, but they never start with a width
+    let (width, height) = words.iter().find_map(|word| {
+        let (width, height) = word.split_once('x')?;
+        Some((width.parse::<u32>().ok().filter(|width| *width > 0)?, height.parse::<u32>().ok()?))
+    })?;
+    let fps = words
+        .windows(2)
+        .find(|pair| pair[1] == "fps")
+        .and_then(|pair| pair[0].parse::<f32>().ok())
+        .unwrap_or(30.0);
+
+    // phone videos are often stored on their side with a rotation tag, which ffmpeg (and the preview) turn upright,
+    // so the size is the one after turning. older ffmpegs print it as `rotate : 90`, newer ones as a display matrix
+    let rotation = info.lines().find_map(|line| {
+        let line = line.trim();
+        match line.split_once("rotation of ") {
+            Some((_, rotation)) => rotation.split_whitespace().next()?.parse::<f32>().ok(),
+            None if line.starts_with("rotate") => line.split_once(':')?.1.trim().parse::<f32>().ok(),
+            None => None,
+        }
+    });
+    if rotation.is_some_and(|rotation| (rotation.abs() as u32 / 90) % 2 == 1) {
+        return Some(VideoInfo { width: height, height: width, fps });
+    }
+    Some(VideoInfo { width, height, fps })
+}
+
+/// Whether `file` has any audio stream at all
+fn has_audio(file: &PathBuf) -> bool {
+    probe(file).is_some_and(|info| info.lines().any(|line| line.contains("Stream #") && line.contains("Audio:")))
+}
+
+/// Reads the duration of a video (in seconds) from what ffmpeg prints about its input
+pub fn probe_duration(file: &PathBuf) -> Option<f32> {
+    let info = probe(file)?;
+    let duration = info.split("Duration: ").nth(1)?.split(',').next()?;
+
+    let mut seconds = 0.0;
+    for part in duration.trim().split(':') {
+        seconds = seconds * 60.0 + part.parse::<f32>().ok()?;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_sizes() {
+        assert_eq!("25M".parse::<TargetSize>(), Ok(TargetSize::Limit(25_000_000)));
+        assert_eq!("8mb".parse::<TargetSize>(), Ok(TargetSize::Limit(8_000_000)));
+        assert_eq!("none".parse::<TargetSize>(), Ok(TargetSize::Unlimited));
+        assert!("0".parse::<TargetSize>().is_err());
+        assert!("big".parse::<TargetSize>().is_err());
+    }
+
+    /// every turn with and without a mirror, vertical flips are checked below
+    #[test]
+    fn video_directions() {
+        let directions = [
+            (0, false, "identity"),
+            (1, false, "90r"),
+            (2, false, "180"),
+            (3, false, "90l"),
+            (0, true, "horiz"),
+            (1, true, "ul-lr"),
+            (2, true, "vert"),
+            (3, true, "ur-ll"),
+        ];
+        for (quarter_turns, flip_horizontal, direction) in directions {
+            let transform = Transform { quarter_turns, flip_horizontal, flip_vertical: false };
+            assert_eq!(transform.video_direction(), direction, "{transform:?}");
+        }
+    }
+
+    #[test]
+    fn vertical_flip_is_a_mirrored_half_turn() {
+        for quarter_turns in 0..4 {
+            for flip_horizontal in [false, true] {
+                let flipped = Transform { quarter_turns, flip_horizontal, flip_vertical: true };
+                let same = Transform { quarter_turns: (quarter_turns + 2) % 4, flip_horizontal: !flip_horizontal, flip_vertical: false };
+                assert_eq!(flipped.video_direction(), same.video_direction(), "{flipped:?}");
+            }
+        }
+    }
+}
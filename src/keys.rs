@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+
+/// Everything that can be bound to a key in the `[keys]` table of the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PlayPause,
+    StepBack,
+    StepForward,
+    PreviousKeyframe,
+    NextKeyframe,
+    SetIn,
+    SetOut,
+    JumpToStart,
+    JumpToEnd,
+    ShuttleBack,
+    ShuttlePause,
+    ShuttleForward,
+    Slower,
+    Faster,
+    ToggleLoop,
+    Open,
+    AddSegment,
+    RemoveSegment,
+    Export,
+    Restart,
+    Cancel,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "play_pause" => Action::PlayPause,
+            "step_back" => Action::StepBack,
+            "step_forward" => Action::StepForward,
+            "previous_keyframe" => Action::PreviousKeyframe,
+            "next_keyframe" => Action::NextKeyframe,
+            "set_in" => Action::SetIn,
+            "set_out" => Action::SetOut,
+            "jump_to_start" => Action::JumpToStart,
+            "jump_to_end" => Action::JumpToEnd,
+            "shuttle_back" => Action::ShuttleBack,
+            "shuttle_pause" => Action::ShuttlePause,
+            "shuttle_forward" => Action::ShuttleForward,
+            "slower" => Action::Slower,
+            "faster" => Action::Faster,
+            "toggle_loop" => Action::ToggleLoop,
+            "open" => Action::Open,
+            "add_segment" => Action::AddSegment,
+            "remove_segment" => Action::RemoveSegment,
+            "export" => Action::Export,
+            "restart" => Action::Restart,
+            "cancel" => Action::Cancel,
+            _ => return Err(format!("unknown action \"{s}\"")),
+        })
+    }
+}
+
+/// A key along with the modifiers that have to be held for it, written like `ctrl+r` or `shift+left`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    key: Key<String>,
+    modifiers: Modifiers,
+}
+
+impl Chord {
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        if self.modifiers != modifiers {
+            return false;
+        }
+        match (&self.key, key.as_ref()) {
+            (Key::Named(bound), Key::Named(pressed)) => *bound == pressed,
+            // shift turns letters uppercase, which shouldnt stop `shift+s` from matching
+            (Key::Character(bound), Key::Character(pressed)) => bound.eq_ignore_ascii_case(pressed),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        // the key is whatever comes after the last +, unless the key is + itself
+        let (modifiers, key) = match s.rsplit_once('+') {
+            Some((modifiers, "")) => (modifiers.strip_suffix('+').unwrap_or(modifiers), "+"),
+            Some(split) => split,
+            None => ("", s.as_str()),
+        };
+
+        let mut chord = Chord {
+            key: named_key(key).map(Key::Named).unwrap_or_else(|| Key::Character(key.to_string())),
+            modifiers: Modifiers::empty(),
+        };
+        if let Key::Character(character) = &chord.key {
+            if character.chars().count() != 1 {
+                return Err(format!("unknown key \"{key}\""));
+            }
+        }
+
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            chord.modifiers.insert(match modifier {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" => Modifiers::ALT,
+                "super" | "logo" | "cmd" => Modifiers::LOGO,
+                _ => return Err(format!("unknown modifier \"{modifier}\"")),
+            });
+        }
+        Ok(chord)
+    }
+}
+
+fn named_key(name: &str) -> Option<Named> {
+    Some(match name {
+        "space" => Named::Space,
+        "enter" | "return" => Named::Enter,
+        "tab" => Named::Tab,
+        "escape" | "esc" => Named::Escape,
+        "backspace" => Named::Backspace,
+        "delete" | "del" => Named::Delete,
+        "insert" => Named::Insert,
+        "left" => Named::ArrowLeft,
+        "right" => Named::ArrowRight,
+        "up" => Named::ArrowUp,
+        "down" => Named::ArrowDown,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ => return None,
+    })
+}
+
+/// Which action every chord does, an action can have any number of chords
+#[derive(Debug, Clone)]
+pub struct Keys {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        let bindings = [
+            ("space", Action::PlayPause),
+            ("left", Action::StepBack),
+            (",", Action::StepBack),
+            ("right", Action::StepForward),
+            (".", Action::StepForward),
+            ("shift+left", Action::PreviousKeyframe),
+            ("shift+right", Action::NextKeyframe),
+            ("i", Action::SetIn),
+            ("o", Action::SetOut),
+            ("home", Action::JumpToStart),
+            ("end", Action::JumpToEnd),
+            ("j", Action::ShuttleBack),
+            ("k", Action::ShuttlePause),
+            ("l", Action::ShuttleForward),
+            ("[", Action::Slower),
+            ("]", Action::Faster),
+            ("ctrl+l", Action::ToggleLoop),
+            ("ctrl+o", Action::Open),
+            ("s", Action::AddSegment),
+            ("delete", Action::RemoveSegment),
+            ("backspace", Action::RemoveSegment),
+            ("ctrl+e", Action::Export),
+            ("ctrl+r", Action::Restart),
+            ("escape", Action::Cancel),
+        ];
+
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(chord, action)| (chord.parse().unwrap(), action))
+                .collect(),
+        }
+    }
+}
+
+impl Keys {
+    /// Replaces every chord of `action` with `chords`, an empty list unbinds it
+    pub fn bind(&mut self, action: Action, chords: Vec<Chord>) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bindings.extend(chords.into_iter().map(|chord| (chord, action)));
+    }
+
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(chord, _)| chord.matches(key, modifiers))
+            .map(|(_, action)| *action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(key: &str, modifiers: Modifiers) -> Chord {
+        Chord { key: Key::Character(key.to_string()), modifiers }
+    }
+
+    #[test]
+    fn plus_can_be_the_key() {
+        assert_eq!("+".parse::<Chord>(), Ok(chord("+", Modifiers::empty())));
+        assert_eq!("ctrl++".parse::<Chord>(), Ok(chord("+", Modifiers::CTRL)));
+    }
+
+    #[test]
+    fn letters_are_lowercased() {
+        assert_eq!("shift+S".parse::<Chord>(), Ok(chord("s", Modifiers::SHIFT)));
+    }
+
+    #[test]
+    fn named_keys_and_modifiers() {
+        assert_eq!(
+            "ctrl+shift+left".parse::<Chord>(),
+            Ok(Chord { key: Key::Named(Named::ArrowLeft), modifiers: Modifiers::CTRL | Modifiers::SHIFT })
+        );
+        assert!("hyper+s".parse::<Chord>().is_err());
+        assert!("ctrl+nope".parse::<Chord>().is_err());
+    }
+}
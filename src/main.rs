@@ -1,21 +1,24 @@
 use std::io::{BufReader, Read, Seek};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::{env::home_dir, path::PathBuf, string, time::Duration};
 
-use std::os::unix::fs::MetadataExt;
-use essi_ffmpeg::FFmpeg;
 use freedesktop_icons::lookup;
 use iced::advanced::graphics::image::image_rs::ImageFormat;
 use iced::futures::stream;
 use iced::window::frames;
 use iced::window::settings::PlatformSpecific;
-use iced::{widget::{button, Column, Container, Row, Svg}, window::Settings, Alignment, Application, Background, Border, Color, ContentFit, Font, Length, Padding, Shadow, Task};
+use iced::{widget::{button, Column, Container, Row, Svg}, window::Settings, Alignment, Application, Background, Border, Color, ContentFit, Font, Length, Padding, Shadow, Task, Theme};
 use iced_video_player::{Position, Video, VideoPlayer};
 use iced::{widget, Subscription};
 use notify_rust::Notification;
 use rfd::FileDialog;
-use timeline::{hex_to_rgb, hex_to_rgba, Timeline};
+use export::{Animation, AnimationKind, AudioCodec, AudioFormat, AudioOnly, Dither, ExportError, ExportSettings, Format, Output, Picture, Progress, TargetSize, Transform, VideoCodec, VideoInfo};
+use keys::{Action, Chord, Keys};
+use timeline::{hex_to_rgb, hex_to_rgba, Segment, Timeline};
+use crop::{Aspect, Crop, CropArea};
 use toml::Table;
 use std::fs::{self, read_to_string, File};
 
@@ -23,17 +26,106 @@ use gstreamer as gst;
 use gstreamer_app as gst_app;
 use gstreamer_app::prelude::*;
 
+mod crop;
+mod export;
+mod keys;
+mod preview;
 mod timeline;
 
-use clap::Parser;
-/// Simple video trimmer that automatically compresses a video if its above 10mb size
+use clap::{Parser, Subcommand};
+/// Simple video trimmer that automatically compresses a video if its above the target size
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// The file you want to edit
-    file: Option<String>
+    /// The files you want to edit, they are listed next to the video when there is more than one
+    files: Vec<String>,
+
+    /// The size exported videos have to fit in, e.g. 25M, or none to never compress
+    #[arg(long, global = true)]
+    target: Option<TargetSize>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Trims (and compresses if needed) a video without opening the editor
+    Export {
+        /// The file you want to trim
+        file: String,
+        /// Where the clip starts, as seconds or [hh:]mm:ss.ms
+        #[arg(long, value_parser = parse_timestamp, default_value = "0")]
+        start: f32,
+        /// Where the clip ends, defaults to the end of the video
+        #[arg(long, value_parser = parse_timestamp)]
+        end: Option<f32>,
+        /// The file the clip is saved to, its extension picks what gets exported (mp4, webm, mkv, gif, webp, opus, mp3, flac or wav)
+        #[arg(short, long)]
+        output: String,
+        /// Cut without re-encoding, the clip then starts at the keyframe before --start
+        #[arg(long)]
+        copy: bool,
+        /// The height of the clip (the width when its upright), picked from the bitrate when left out
+        #[arg(long)]
+        height: Option<u32>,
+        /// The framerate of the clip, picked from the bitrate when left out
+        #[arg(long)]
+        fps: Option<u32>,
+    },
+}
+
+fn parse_timestamp(value: &str) -> Result<f32, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("`{value}` is not a valid timestamp"));
+    }
+
+    let mut seconds = 0.0;
+    for part in parts {
+        let part = part.parse::<f32>().map_err(|_| format!("`{value}` is not a valid timestamp"))?;
+        if part < 0.0 {
+            return Err(format!("`{value}` is not a valid timestamp"));
+        }
+        seconds = seconds * 60.0 + part;
+    }
+    Ok(seconds)
+}
+
+/// Expands `~` and turns relative paths into absolute ones
+fn expand_path(path: &str) -> PathBuf {
+    let path = match path.strip_prefix("~") {
+        Some(rest) => home_dir().unwrap().join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    };
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// What gets exported to `path`, picked from its extension and filled in from the config
+fn output_for(path: &PathBuf, config: &Config) -> Result<Output, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "gif" => Ok(Output::Animation(Animation { kind: AnimationKind::Gif, ..config.animation })),
+        "webp" => Ok(Output::Animation(Animation { kind: AnimationKind::Webp, ..config.animation })),
+        _ => {
+            if let Ok(container) = extension.parse::<export::Container>() {
+                // the configured codecs are kept when the container can hold them
+                if config.format.container == container {
+                    return Ok(Output::Video(config.format));
+                }
+                let (video, audio) = container.default_codecs();
+                return Ok(Output::Video(Format::new(container, video, audio).unwrap()));
+            }
+            if let Ok(format) = extension.parse::<AudioFormat>() {
+                return Ok(Output::Audio(AudioOnly { format, ..config.audio_only }));
+            }
+            Err(format!(
+                "couldnt tell what to export from `{}`, end it with mp4, webm, mkv, gif, webp, opus, mp3, flac or wav",
+                path.display()
+            ))
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Config {
@@ -43,6 +135,15 @@ pub struct Config {
     hover_background: String,
     font: String,
     audio: Option<String>,
+    target_size: TargetSize,
+    format: Format,
+    /// what gifs and webps start out as
+    animation: Animation,
+    /// what audio only exports are written as
+    audio_only: AudioOnly,
+    /// what exports are picked as at the start
+    output: Output,
+    keys: Keys,
 
 }
 
@@ -54,7 +155,13 @@ impl Default for Config {
             timeline_color: "#829f62".to_string(),
             hover_background: "#0E0E0E".to_string(),
             font: "EPSON 正楷書体Ｍ".to_string(),
-            audio: None
+            audio: None,
+            target_size: TargetSize::default(),
+            format: Format::default(),
+            animation: Animation::default(),
+            audio_only: AudioOnly::default(),
+            output: Output::default(),
+            keys: Keys::default(),
         }
     }
 }
@@ -89,24 +196,121 @@ fn main() {
         if let Some(audio) = toml.get("notification_audio") {
             config.audio = Some(audio.as_str().unwrap().to_string());
         }
+        if let Some(target) = toml.get("target_size") {
+            config.target_size = match target.as_integer() {
+                Some(bytes) => match u64::try_from(bytes) {
+                    Ok(bytes) if bytes > 0 => TargetSize::Limit(bytes),
+                    _ => panic!("`{bytes}` is not a valid target_size, try something like 25M or \"none\""),
+                },
+                None => target.as_str().unwrap().parse().unwrap(),
+            };
+        }
+        let container = toml.get("container").map(|container| container.as_str().unwrap().parse::<export::Container>().unwrap());
+        let video_codec = toml.get("video_codec").map(|codec| codec.as_str().unwrap().parse::<VideoCodec>().unwrap());
+        let audio_codec = toml.get("audio_codec").map(|codec| codec.as_str().unwrap().parse::<AudioCodec>().unwrap());
+        if container.is_some() || video_codec.is_some() || audio_codec.is_some() {
+            // codecs that arent given are the usual ones for the container
+            let container = container.unwrap_or(config.format.container);
+            let (video, audio) = container.default_codecs();
+            config.format = Format::new(container, video_codec.unwrap_or(video), audio_codec.unwrap_or(audio)).unwrap();
+        }
+        if let Some(fps) = toml.get("animation_fps") {
+            config.animation.fps = fps.as_integer().unwrap() as u32;
+        }
+        if let Some(width) = toml.get("animation_width") {
+            config.animation.width = width.as_integer().unwrap() as u32;
+        }
+        if let Some(dither) = toml.get("gif_dither") {
+            config.animation.dither = dither.as_str().unwrap().parse::<Dither>().unwrap();
+        }
+        if let Some(format) = toml.get("audio_format") {
+            config.audio_only.format = format.as_str().unwrap().parse::<AudioFormat>().unwrap();
+        }
+        if let Some(bitrate) = toml.get("audio_bitrate") {
+            config.audio_only.bitrate = bitrate.as_integer().unwrap() as u32;
+        }
+        config.output = match toml.get("export_as").map(|output| output.as_str().unwrap()) {
+            None | Some("video") => Output::Video(config.format),
+            Some("gif") => Output::Animation(Animation { kind: AnimationKind::Gif, ..config.animation }),
+            Some("webp") => Output::Animation(Animation { kind: AnimationKind::Webp, ..config.animation }),
+            Some("audio") => Output::Audio(config.audio_only),
+            Some(output) => panic!("`{output}` is not something to export as, try video, gif, webp or audio"),
+        };
+        if let Some(keys) = toml.get("keys") {
+            // every action takes a chord or a list of them, replacing its default ones
+            for (action, chords) in keys.as_table().expect("[keys] has to be a table") {
+                let action: Action = action.parse().unwrap();
+                let chords = match chords.as_array() {
+                    Some(chords) => chords.iter().map(|chord| chord.as_str().unwrap().parse::<Chord>().unwrap()).collect(),
+                    None => vec![chords.as_str().unwrap().parse::<Chord>().unwrap()],
+                };
+                config.keys.bind(action, chords);
+            }
+        }
+    }
+    if let Some(target) = cli.target {
+        config.target_size = target;
     }
 
-    let mut file = None;
-    if let Some(cli_file) = cli.file {
-        let mut path = PathBuf::from("/");
+    if let Some(Command::Export { file, start, end, output, copy, height, fps }) = cli.command {
+        let old_file = expand_path(&file);
+        let duration = export::probe_duration(&old_file);
+        let end = end
+            .or(duration)
+            .expect("Couldnt read the duration of the video, try passing --end");
+        if end <= start {
+            eprintln!("--end has to be after --start");
+            std::process::exit(1);
+        }
+        if let Some(duration) = duration.filter(|duration| end > *duration) {
+            eprintln!("--start and --end have to be inside the video, which is {duration:.2}s long");
+            std::process::exit(1);
+        }
 
-        for text in cli_file.split("/") {
-            if text == "~" {
-                path.push(home_dir().unwrap().to_str().unwrap());
-            } else {
-                path.push(text);
+        let output = expand_path(&output);
+        let kind = match output_for(&output, &config) {
+            Ok(kind) => kind,
+            Err(error) => {
+                eprintln!("{error}");
+                std::process::exit(1);
+            }
+        };
+        let cancel = AtomicBool::new(false);
+        let settings = ExportSettings {
+            segments: vec![Segment { start, end }],
+            target: config.target_size,
+            stream_copy: copy,
+            output: kind,
+            height,
+            fps,
+            crop: None,
+            transform: Transform::default(),
+        };
+        let result = export::export(old_file, output.clone(), settings, &cancel, |progress| {
+            eprint!("\rpass {}/{} {:3.0}%", progress.pass, progress.passes, progress.fraction * 100.0);
+        });
+        eprintln!();
+        let size = match result {
+            Ok(size) => size,
+            Err(error) => {
+                eprintln!("couldnt export the clip: {error}");
+                std::process::exit(1);
+            }
+        };
+        println!("saved {} ({})", output.display(), export::format_size(size));
+        if let TargetSize::Limit(target) = config.target_size {
+            if size > target {
+                eprintln!("couldnt fit the clip in {}", config.target_size);
+                std::process::exit(1);
             }
         }
+        return;
+    }
 
-        file = Some(path);
-    } else {
-        file = Some(FileDialog::new()
-                    .pick_file().unwrap());
+    let mut files: Vec<PathBuf> = cli.files.iter().map(|file| expand_path(file)).collect();
+    if files.is_empty() {
+        files = FileDialog::new()
+                    .pick_files().unwrap();
     }
     let mut settings = Settings::default();
 
@@ -142,48 +346,48 @@ fn main() {
         .default_font(Font::with_name(string_to_static_str(config.font.clone())))
         .subscription(subscription)
         .run_with(move || {
-            let old_file = file.unwrap();
-            let uri = &url::Url::from_file_path(&old_file).unwrap();
-            let video = {
-                gst::init().unwrap();
-
-                let pipeline = format!("playbin uri=\"{}\" text-sink=\"appsink name=iced_text sync=true drop=true\" video-sink=\"videoscale ! videoconvert ! appsink name=iced_video drop=true caps=video/x-raw,format=NV12,pixel-aspect-ratio=1/1,width=1280,height=720\"", uri.as_str());
-                let pipeline = gst::parse::launch(pipeline.as_ref()).unwrap()
-                    .downcast::<gst::Pipeline>()
-                    .map_err(|_| iced_video_player::Error::Cast).unwrap();
-
-                let video_sink: gst::Element = pipeline.property("video-sink");
-                let pad = video_sink.pads().first().cloned().unwrap();
-                let pad = pad.dynamic_cast::<gst::GhostPad>().unwrap();
-                let bin = pad
-                    .parent_element()
-                    .unwrap()
-                    .downcast::<gst::Bin>()
-                    .unwrap();
-                let video_sink = bin.by_name("iced_video").unwrap();
-                let video_sink = video_sink.downcast::<gst_app::AppSink>().unwrap();
-
-                let text_sink: gst::Element = pipeline.property("text-sink");
-                let text_sink = text_sink.downcast::<gst_app::AppSink>().unwrap();
-
-                Video::from_gst_pipeline(pipeline, video_sink, Some(text_sink)).unwrap()
-            };
+            let old_file = files[0].clone();
+            let (video, framerate) = load_video(&old_file, Transform::default()).unwrap_or_else(|error| {
+                eprintln!("couldnt open {}: {error}", old_file.display());
+                std::process::exit(1);
+            });
 
             let state = App {
 
                 video_length: video.duration().as_secs_f32(),
+                framerate,
+                speed: 1.0,
                 cursor_position: 0.0,
                 mouse_position: 0.0,
                 mouse_content: String::new(),
-                start: 0.0,
-                end:  video.duration().as_secs_f32() / 2.0,
+                segments: vec![Segment { start: 0.0, end: video.duration().as_secs_f32() / 2.0 }],
+                selected: 0,
+                selections: HashMap::new(),
+                files,
+                view_start: 0.0,
+                view_end: video.duration().as_secs_f32(),
+                target_size: config.target_size,
+                output: config.output,
+                progress: None,
+                cancel: Arc::new(AtomicBool::new(false)),
+                stream_copy: false,
+                video_info: None,
+                height: None,
+                fps: None,
+                cropping: false,
+                crop: None,
+                aspect: Aspect::Free,
+                transform: Transform::default(),
+                keyframes: Vec::new(),
+                thumbnails: Vec::new(),
+                waveform: Arc::new(Vec::new()),
+                file_size: fs::metadata(&old_file).unwrap().len(),
                 pressed_start: false,
                 pressed_end: false,
                 pressed_anywhere: false,
                 position_loop: false,
-                processing: false,
-                start_loop: false,
-                end_loop: false,
+                queue: Vec::new(),
+                next_job: 0,
                 moving: false,
                 play_icon: lookup("sickle-play-symbolic").find().unwrap().to_str().unwrap().to_string(),
                 pause_icon: lookup("sickle-pause-symbolic").find().unwrap().to_str().unwrap().to_string(),
@@ -195,10 +399,93 @@ fn main() {
                 config,
                 is_flatpak
             };
-            (state, Task::none())
+            let previews = load_previews(&state.old_file, state.video_length);
+            (state, previews)
         });
 }
 
+/// Builds the playbin pipeline that plays `file`, along with its framerate.
+/// `transform` is shown by a videoflip before the frames get scaled, so turning the video just letterboxes it differently.
+/// another one before it turns videos with a rotation tag upright first, the same as ffmpeg does on export
+fn load_video(file: &PathBuf, transform: Transform) -> Result<(Video, f32), String> {
+    let uri = &url::Url::from_file_path(file).map_err(|_| "the path has to be absolute".to_string())?;
+    gst::init().unwrap();
+
+    let pipeline = format!("playbin uri=\"{}\" text-sink=\"appsink name=iced_text sync=true drop=true\" video-sink=\"videoconvert ! videoflip video-direction=auto ! videoflip name=iced_flip video-direction={} ! videoscale ! videoconvert ! appsink name=iced_video drop=true caps=video/x-raw,format=NV12,pixel-aspect-ratio=1/1,width=1280,height=720\"", uri.as_str(), transform.video_direction());
+    let pipeline = gst::parse::launch(pipeline.as_ref())
+        .map_err(|error| error.to_string())?
+        .downcast::<gst::Pipeline>()
+        .map_err(|_| "the pipeline isnt a pipeline".to_string())?;
+
+    let video_sink = video_sink_bin(&pipeline).by_name("iced_video").unwrap();
+    let video_sink = video_sink.downcast::<gst_app::AppSink>().unwrap();
+
+    let text_sink: gst::Element = pipeline.property("text-sink");
+    let text_sink = text_sink.downcast::<gst_app::AppSink>().unwrap();
+
+    // this is where files that arent videos fail
+    let video = Video::from_gst_pipeline(pipeline, video_sink.clone(), Some(text_sink))
+        .map_err(|error| format!("{error:?}"))?;
+    // the caps are only negotiated once the video is loaded
+    Ok((video, framerate(&video_sink)))
+}
+
+/// The bin that playbin was given as its video sink
+fn video_sink_bin(pipeline: &gst::Pipeline) -> gst::Bin {
+    let video_sink: gst::Element = pipeline.property("video-sink");
+    let pad = video_sink.pads().first().cloned().unwrap();
+    let pad = pad.dynamic_cast::<gst::GhostPad>().unwrap();
+    pad
+        .parent_element()
+        .unwrap()
+        .downcast::<gst::Bin>()
+        .unwrap()
+}
+
+/// Shows `transform` in the preview right away, videoflip renegotiates the size on its own
+fn preview_transform(video: &Video, transform: Transform) {
+    let flip = video_sink_bin(&video.pipeline()).by_name("iced_flip").unwrap();
+    flip.set_property_from_str("video-direction", transform.video_direction());
+}
+
+/// Reads the keyframes, filmstrip and waveform of `file` in the background.
+/// They come back along with the file, since another one might have been opened in the meantime
+fn load_previews(file: &PathBuf, duration: f32) -> Task<Messages> {
+    let keyframes_file = file.clone();
+    let keyframes = Task::perform(
+        smol::unblock(move || {
+            let keyframes = export::keyframes(&keyframes_file);
+            (keyframes_file, keyframes)
+        }),
+        |(file, keyframes)| Messages::KeyframesLoaded(file, keyframes)
+    );
+    let thumbnails_file = file.clone();
+    let thumbnails = Task::perform(
+        smol::unblock(move || {
+            let thumbnails = preview::thumbnails(&thumbnails_file, duration);
+            (thumbnails_file, thumbnails)
+        }),
+        |(file, thumbnails)| Messages::ThumbnailsLoaded(file, thumbnails)
+    );
+    let info_file = file.clone();
+    let info = Task::perform(
+        smol::unblock(move || {
+            let info = export::probe_video(&info_file);
+            (info_file, info)
+        }),
+        |(file, info)| Messages::VideoInfoLoaded(file, info)
+    );
+    let waveform_file = file.clone();
+    let waveform = Task::perform(
+        smol::unblock(move || {
+            let waveform = Arc::new(preview::waveform(&waveform_file));
+            (waveform_file, waveform)
+        }),
+        |(file, waveform)| Messages::WaveformLoaded(file, waveform)
+    );
+    Task::batch([keyframes, info, thumbnails, waveform])
+}
+
 struct App {
     video: Video,
     old_file: PathBuf,
@@ -206,15 +493,31 @@ struct App {
     mouse_content: String,
     cursor_position: f32,
     video_length: f32,
+    /// frames per second of the preview, read from what gstreamer negotiated
+    framerate: f32,
+    /// how fast (and which way) the video plays, changed by shuttling
+    speed: f64,
     video_time: time::Duration,
     config: Config,
 
-    start: f32,
-    end: f32,
-
+    /// the parts of the video that are kept, sorted and never overlapping
+    segments: Vec<Segment>,
+    /// the segment that the handles and arrow keys are editing
+    selected: usize,
+    /// the selections of the files that were open before
+    selections: HashMap<PathBuf, Vec<Segment>>,
+    /// every file that was opened, shown as a list to switch between them
+    files: Vec<PathBuf>,
+    /// the part of the video the timeline is zoomed into, in seconds
+    view_start: f32,
+    view_end: f32,
+    /// how big the export is allowed to be, starts out as the one from the config
+    target_size: TargetSize,
+    /// what the export gets encoded as, starts out as the one from the config
+    output: Output,
+
+    /// plays the selection over and over, skipping whatever is between the segments
     position_loop: bool,
-    start_loop: bool,
-    end_loop: bool,
     moving: bool,
 
     position_value: f32,
@@ -228,7 +531,45 @@ struct App {
     play_icon: String,
     pause_icon: String,
     trim_icon: String,
-    processing: bool,
+    /// exports waiting for their turn, the first one is the one running
+    queue: Vec<Job>,
+    /// the id the next export gets
+    next_job: usize,
+    /// the last progress the running export reported
+    progress: Option<Progress>,
+    /// set to stop the running export, every export gets a new one
+    cancel: Arc<AtomicBool>,
+    /// export without re-encoding, only used when the selection fits in the target anyway
+    stream_copy: bool,
+    /// the size and framerate of the video, `None` until its read in the background (or if ffmpeg couldnt read it)
+    video_info: Option<VideoInfo>,
+    /// overrides for the height and framerate of the export, picked from the bitrate when `None`
+    height: Option<u32>,
+    fps: Option<u32>,
+    /// shows the crop area over the video
+    cropping: bool,
+    /// the part of the frame that is exported, `None` keeps all of it
+    crop: Option<Crop>,
+    /// the shape new crops are held to
+    aspect: Aspect,
+    /// turns and flips of the frame, the preview shows them too
+    transform: Transform,
+    /// timestamps of the keyframes of the video, empty until they are read in the background
+    keyframes: Vec<f32>,
+    /// the filmstrip of the timeline, empty until it is generated (or read from the cache) in the background
+    thumbnails: Vec<Option<widget::image::Handle>>,
+    /// peaks of the audio, in an arc since the timeline gets a copy every frame
+    waveform: Arc<Vec<f32>>,
+    file_size: u64,
+}
+
+/// A video waiting to be exported, with the selection it had when export was pressed
+#[derive(Debug, Clone)]
+struct Job {
+    id: usize,
+    file: PathBuf,
+    output: PathBuf,
+    settings: ExportSettings,
 }
 
 #[derive(Debug, Clone)]
@@ -237,6 +578,32 @@ enum Messages {
     PlayPause,
     PressedStart(bool),
     PressedEnd(bool),
+    SelectSegment(usize),
+    SetIn,
+    SetOut,
+    Shuttle(i8),
+    ChangeSpeed(i8),
+    ToggleLoop,
+    SetView(f32, f32),
+    AddSegment,
+    RemoveSegment,
+    SetTargetSize(TargetSize),
+    SetOutput(Output),
+    ToggleStreamCopy,
+    KeyframesLoaded(PathBuf, Vec<f32>),
+    VideoInfoLoaded(PathBuf, Option<VideoInfo>),
+    SetHeight(Option<u32>),
+    ToggleCrop,
+    SetCrop(Option<Crop>),
+    SetAspect(Aspect),
+    Rotate,
+    Flip { horizontal: bool },
+    SetFps(Option<u32>),
+    ThumbnailsLoaded(PathBuf, Vec<Option<PathBuf>>),
+    WaveformLoaded(PathBuf, Arc<Vec<f32>>),
+    OpenDialog,
+    /// adds the files to the clip list and switches to the first one
+    Open(Vec<PathBuf>),
     Pressed(bool),
     UpdateStart(f32),
     UpdateEnd(f32),
@@ -248,11 +615,49 @@ enum Messages {
     MouseMove(f32),
     PositionalUpdate(f32),
     RestartStream,
-    ProcessingDone,
+    ExportProgress(usize, Progress),
+    CancelExport,
+    Queue(Job),
+    Unqueue(usize),
+    /// the id of the job and the size of what it wrote
+    ProcessingDone(usize, u64),
+    ExportFailed(usize, String),
     Export
 }
 
 
+/// Frames per second of whatever the appsink is being fed, falls back to 30 for variable framerate videos
+fn framerate(video_sink: &gst_app::AppSink) -> f32 {
+    video_sink.static_pad("sink")
+        .and_then(|pad| pad.current_caps())
+        .and_then(|caps| caps.structure(0).and_then(|structure| structure.get::<gst::Fraction>("framerate").ok()))
+        .map(|framerate| framerate.numer() as f32 / framerate.denom() as f32)
+        .filter(|framerate| framerate.is_finite() && *framerate > 0.0)
+        .unwrap_or(30.0)
+}
+
+/// Shows a desktop notification
+fn notify(app: &App, body: &str) {
+    let _ = Notification::new()
+        .summary("sickle")
+        .body(body)
+        .icon(if app.is_flatpak { "com.github.vnuxa.sickle" } else { "sickle" })
+        .show();
+}
+
+/// The size of `file`, or why it cant be opened
+fn file_size(file: &PathBuf) -> Result<u64, String> {
+    let metadata = fs::metadata(file).map_err(|error| error.to_string())?;
+    if metadata.is_file() {
+        Ok(metadata.len())
+    } else {
+        Err("its not a file".to_string())
+    }
+}
+
+/// the playback rates the speed buttons step through, shuttling also stops at the last one
+const SPEEDS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
 fn string_to_static_str(s: String) -> &'static str {
     Box::leak(s.into_boxed_str())
 }
@@ -273,18 +678,38 @@ impl Default for App {
         Self {
 
             video_length: video.duration().as_secs_f32(),
+            framerate: 30.0,
+            speed: 1.0,
             cursor_position: 0.0,
             mouse_position: 0.0,
             mouse_content: String::new(),
-            start: 0.0,
+            segments: vec![Segment { start: 0.0, end: video.duration().as_secs_f32() / 2.0 }],
+            selected: 0,
+            selections: HashMap::new(),
+            files: Vec::new(),
+            view_start: 0.0,
+            view_end: video.duration().as_secs_f32(),
+            target_size: TargetSize::default(),
+            output: Output::default(),
+            progress: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            stream_copy: false,
+            video_info: None,
+            height: None,
+            fps: None,
+            cropping: false,
+            crop: None,
+            aspect: Aspect::Free,
+            transform: Transform::default(),
+            keyframes: Vec::new(),
+            thumbnails: Vec::new(),
+            waveform: Arc::new(Vec::new()),
+            file_size: 0,
             position_loop: false,
-            start_loop: false,
-            end_loop: false,
             is_flatpak: false,
             position_value: 0.0,
             moving: false,
             old_file: PathBuf::new(),
-            end:  video.duration().as_secs_f32() / 2.0,
             pressed_start: false,
             pressed_end: false,
             pressed_anywhere: false,
@@ -293,7 +718,8 @@ impl Default for App {
             play_icon: lookup("sickle-play-symbolic").find().unwrap().to_str().unwrap().to_string(),
             pause_icon: lookup("sickle-pause-symbolic").find().unwrap().to_str().unwrap().to_string(),
             trim_icon: lookup("sickle-scissors-symbolic").find().unwrap().to_str().unwrap().to_string(),
-            processing: false,
+            queue: Vec::new(),
+            next_job: 0,
             video,
 
         }
@@ -301,26 +727,294 @@ impl Default for App {
 }
 
 
+impl App {
+    /// Moves the start of the selected segment, it cant go past its own end or the segment before it
+    fn set_start(&mut self, position: f32) -> f32 {
+        let min = if self.selected > 0 { self.segments[self.selected - 1].end } else { 0.0 };
+        let segment = &mut self.segments[self.selected];
+        segment.start = position.clamp(min, segment.end);
+        segment.start
+    }
+
+    /// Guesses how big the selection is by assuming the bitrate is the same across the whole video
+    fn estimated_size(&self) -> u64 {
+        let selection: f32 = self.segments.iter().map(Segment::duration).sum();
+        (self.file_size as f64 * (selection / self.video_length) as f64) as u64
+    }
+
+    /// Whether the selection can be stream copied, which is only worth it if it doesnt need compressing.
+    /// Gifs, webps, audio only exports and cropped or turned videos always get encoded
+    fn can_stream_copy(&self) -> bool {
+        if !matches!(self.output, Output::Video(_)) || self.crop.is_some() || !self.transform.is_identity() {
+            return false;
+        }
+        match self.target_size {
+            TargetSize::Limit(target) => self.estimated_size() <= target,
+            TargetSize::Unlimited => true,
+        }
+    }
+
+    /// Where a stream copy of the selected segment actually starts and ends, the start moves back to
+    /// the keyframe before it while the end stays where it is since copied packets can stop on any frame
+    fn stream_copy_cut(&self) -> (f32, f32) {
+        let segment = &self.segments[self.selected];
+        (export::keyframe_before(&self.keyframes, segment.start), segment.end)
+    }
+
+    fn export_settings(&self) -> ExportSettings {
+        ExportSettings {
+            segments: self.segments.clone(),
+            target: self.target_size,
+            stream_copy: self.stream_copy && self.can_stream_copy(),
+            output: self.output,
+            height: self.height,
+            fps: self.fps,
+            crop: self.crop,
+            transform: self.transform,
+        }
+    }
+
+    /// The size and framerate of the video as the preview shows it, turned
+    fn turned_info(&self) -> Option<VideoInfo> {
+        self.video_info.map(|info| self.transform.apply(info))
+    }
+
+    /// The size and framerate of what gets exported, which is smaller than the video when its cropped
+    fn export_info(&self) -> Option<VideoInfo> {
+        self.turned_info().map(|info| self.crop.map_or(info, |crop| crop.apply(info)))
+    }
+
+    /// What a re-encoded video export would end up at with `height` and `fps` as overrides,
+    /// `None` for other outputs and until the size of the video is read
+    fn picture(&self, height: Option<u32>, fps: Option<u32>) -> Option<Picture> {
+        let Output::Video(format) = self.output else {
+            return None;
+        };
+        let duration = self.segments.iter().map(Segment::duration).sum();
+        self.export_info().map(|info| export::picture(info, format, self.target_size, duration, height, fps))
+    }
+
+    /// Where playback has to jump to when looping, if the playhead left the segments.
+    /// After the last segment it goes back to the first one (or the other way around when playing backwards)
+    fn loop_target(&self) -> Option<f32> {
+        let position = self.cursor_position;
+        if !self.position_loop || self.video.paused() {
+            return None;
+        }
+        if self.segments.iter().any(|segment| segment.start <= position && position <= segment.end) {
+            return None;
+        }
+
+        if self.speed > 0.0 {
+            let next = self.segments.iter().find(|segment| segment.start > position).unwrap_or(&self.segments[0]);
+            Some(next.start)
+        } else {
+            let previous = self.segments.iter().rev().find(|segment| segment.end < position).unwrap_or(&self.segments[self.segments.len() - 1]);
+            Some(previous.end)
+        }
+    }
+
+    /// Swaps the video for `file`. The selection of the file being closed is remembered,
+    /// so opening it again later picks up where it was left. If it cant be played the current video stays open
+    /// and a notification says why
+    fn open(&mut self, file: PathBuf) -> Task<Messages> {
+        let loaded = file_size(&file)
+            .and_then(|size| load_video(&file, Transform::default()).map(|(video, framerate)| (video, framerate, size)));
+        let (video, framerate, file_size) = match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                self.files.retain(|open| *open != file);
+                notify(self, &format!("{} couldnt be opened: {}", file.display(), error));
+                return Task::none();
+            }
+        };
+        self.selections.insert(self.old_file.clone(), self.segments.clone());
+
+        self.video = video;
+        self.framerate = framerate;
+        self.speed = 1.0;
+        self.video_length = self.video.duration().as_secs_f32();
+        self.video_time = time::Duration::seconds_f32(self.video_length);
+        self.file_size = file_size;
+        self.video.set_looping(self.position_loop);
+
+        self.segments = self.selections
+            .get(&file)
+            .cloned()
+            .unwrap_or_else(|| vec![Segment { start: 0.0, end: self.video_length / 2.0 }]);
+        self.selected = 0;
+        self.cursor_position = 0.0;
+        self.view_start = 0.0;
+        self.view_end = self.video_length;
+        self.keyframes = Vec::new();
+        self.video_info = None;
+        // the overrides and the crop might not even fit the next video
+        self.height = None;
+        self.fps = None;
+        self.crop = None;
+        self.transform = Transform::default();
+        self.thumbnails = Vec::new();
+        self.waveform = Arc::new(Vec::new());
+        self.old_file = file;
+        load_previews(&self.old_file, self.video_length)
+    }
+
+    /// Takes the running export off the queue, which starts the next one
+    fn finish_job(&mut self) -> Job {
+        self.progress = None;
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.queue.remove(0)
+    }
+
+    /// Changes the playback rate, negative speeds play backwards.
+    /// If the video cant play at `speed` (some formats cant seek backwards) the old speed is kept
+    fn set_speed(&mut self, speed: f64) {
+        if speed == self.speed {
+            return;
+        }
+        match self.video.set_speed(speed) {
+            Ok(()) => self.speed = speed,
+            Err(error) => notify(self, &format!("couldnt play at {}x: {:?}", speed, error)),
+        }
+    }
+
+    /// Moves the end of the selected segment, it cant go before its own start or past the segment after it
+    fn set_end(&mut self, position: f32) -> f32 {
+        let max = self.segments.get(self.selected + 1).map(|segment| segment.start).unwrap_or(self.video_length);
+        let segment = &mut self.segments[self.selected];
+        segment.end = position.clamp(segment.start, max);
+        segment.end
+    }
+}
+
+/// The files that are open and the exports that are queued, shown next to the video
+fn clip_list(app: &App) -> iced::Element<Messages> {
+    let mut clips = Column::new().spacing(6.0);
+    for file in &app.files {
+        let current = *file == app.old_file;
+        let mut label = file.file_name().unwrap().to_string_lossy().to_string();
+        if app.queue.first().is_some_and(|job| job.file == *file) {
+            label.push_str("  (exporting)");
+        } else if app.queue.iter().any(|job| job.file == *file) {
+            label.push_str("  (queued)");
+        }
+
+        clips = clips.push(
+            button::Button::new(widget::text(label))
+                .style(toggle_button_style(app, current))
+                .width(Length::Fill)
+                .on_press(Messages::Open(vec![file.clone()]))
+        );
+    }
+
+    for (index, job) in app.queue.iter().enumerate() {
+        let name = job.file.file_name().unwrap().to_string_lossy().to_string();
+        let details = if index > 0 {
+            format!("{name}  waiting")
+        } else {
+            match app.progress {
+                Some(progress) => {
+                    let mut details = format!("{name}  pass {}/{}  {:.0}%", progress.pass, progress.passes, progress.fraction * 100.0);
+                    if let Some(eta) = progress.eta {
+                        let eta = eta.as_secs();
+                        details.push_str(&format!("  {:02}:{:02} left", eta / 60, eta % 60));
+                    }
+                    details
+                }
+                None => format!("{name}  starting"),
+            }
+        };
+
+        let mut entry = Column::new()
+            .push(
+                Row::new()
+                    .push(
+                        widget::text(details)
+                            .color(hex_to_rgba(&app.config.main_color, 0.75))
+                            .width(Length::Fill)
+                    )
+                    .push(
+                        // removing the running export cancels it
+                        button::Button::new(widget::text("x"))
+                            .style(button_style(app))
+                            .on_press(Messages::Unqueue(job.id))
+                    )
+                    .spacing(6.0)
+                    .align_y(Alignment::Center)
+            )
+            .spacing(4.0);
+        if index == 0 {
+            entry = entry.push(
+                widget::progress_bar(0.0..=1.0, app.progress.map(|progress| progress.fraction).unwrap_or(0.0))
+                    .height(Length::Fixed(6.0))
+                    .style(|theme| {
+                        widget::progress_bar::Style {
+                            background: Background::Color(hex_to_rgba(&app.config.main_color, 0.15)),
+                            bar: Background::Color(hex_to_rgba(&app.config.main_color, 0.75)),
+                            border: Border::default().rounded(3.0),
+                        }
+                    })
+            );
+        }
+        clips = clips.push(entry);
+    }
+
+    Container::new(widget::scrollable(clips))
+        .width(Length::Fixed(260.0))
+        .height(Length::Fill)
+        .padding(6.0)
+        .into()
+}
+
 fn view(app: &App) -> iced::Element<Messages> {
     let time = time::Duration::seconds_f32(app.cursor_position);
+    let stream_copy = app.stream_copy && app.can_stream_copy();
+    // the size and framerate of a re-encoded export, which stream copies and audio dont have
+    let picture_info = app.export_info().filter(|_| app.picture(None, None).is_some() && !stream_copy);
     Column::new()
         .push(
-            Container::new(
-                VideoPlayer::new(&app.video)
-                    .width(Length::Fill)
-                    .height(Length::Fill)
-                    .content_fit(ContentFit::Contain)
-                    .on_new_frame(Messages::NewFrame),
-
-            )
-                .align_x(Alignment::Center)
-                .align_y(Alignment::Center)
-                .width(Length::Fill)
+            Row::new()
+                .push(
+                    widget::Stack::new()
+                        .push(
+                            Container::new(
+                                VideoPlayer::new(&app.video)
+                                    .width(Length::Fill)
+                                    .height(Length::Fill)
+                                    .content_fit(ContentFit::Contain)
+                                    .on_new_frame(Messages::NewFrame),
+
+                            )
+                                .align_x(Alignment::Center)
+                                .align_y(Alignment::Center)
+                                .width(Length::Fill)
+                                .height(Length::Fill)
+                        )
+                        // the crop is drawn in the same spot as the video, which needs its size to be known
+                        .push_maybe(app.turned_info().filter(|_| app.cropping).map(|info| {
+                            CropArea {
+                                crop: app.crop,
+                                aspect: app.aspect,
+                                source: (info.width, info.height),
+                                frame: app.video.size(),
+                                config: app.config.clone(),
+                                on_change: Box::new(Messages::SetCrop),
+                            }
+                        }))
+                        .width(Length::Fill)
+                        .height(Length::Fill)
+                )
+                .push_maybe((app.files.len() > 1 || !app.queue.is_empty()).then(|| clip_list(app)))
                 .height(Length::Fill)
 
         )
         .push(
             Row::new()
+                .push(
+                    button::Button::new(widget::text("open"))
+                        .style(button_style(app))
+                        .on_press(Messages::OpenDialog)
+                )
                 .push(
                     button::Button::new(
                         Svg::from_path( if app.video.paused() {
@@ -339,14 +1033,7 @@ fn view(app: &App) -> iced::Element<Messages> {
                                 }
                             })
                     )
-                        .style(|state, theme| {
-                            widget::button::Style {
-                                background: Some(Background::Color(hex_to_rgba(&app.config.main_color, 0.15))),
-                                text_color: hex_to_rgba(&app.config.main_color, 0.75),
-                                border: Border::default().rounded(10.0),
-                                shadow: Shadow::default(),
-                            }
-                        })
+                        .style(button_style(app))
                         .height(Length::Fixed(40.0))
                         .width(Length::Fixed(40.0))
                         .on_press(Messages::PlayPause),
@@ -366,17 +1053,46 @@ fn view(app: &App) -> iced::Element<Messages> {
 
 
                     )
+                .push(
+                    button::Button::new(widget::text("-"))
+                        .style(button_style(app))
+                        .on_press(Messages::ChangeSpeed(-1))
+                )
+                .push(
+                    widget::text(format!("{}x", app.speed))
+                        .color(hex_to_rgba(&app.config.main_color, if app.speed == 1.0 { 0.5 } else { 1.0 }))
+                )
+                .push(
+                    button::Button::new(widget::text("+"))
+                        .style(button_style(app))
+                        .on_press(Messages::ChangeSpeed(1))
+                )
+                .push(
+                    button::Button::new(widget::text("loop"))
+                        .style(toggle_button_style(app, app.position_loop))
+                        .on_press(Messages::ToggleLoop)
+                )
 
                     // button("pause").on_press(Messages::PlayPause))
                 .push(
                     Timeline {
                         config: app.config.clone(),
                         duration: app.video_length,
+                        framerate: app.framerate,
                         mouse: app.mouse_position,
                         mouse_content: app.mouse_content.clone(),
                         mouse_move: Box::new(|position| Messages::MouseMove(position)),
-                        start: app.start,
-                        end: app.end,
+                        segments: app.segments.clone(),
+                        selected: app.selected,
+                        view_start: app.view_start,
+                        view_end: app.view_end,
+                        set_view: Box::new(|start, end| Messages::SetView(start, end)),
+                        keyframes: app.keyframes.clone(),
+                        thumbnails: app.thumbnails.clone(),
+                        waveform: app.waveform.clone(),
+                        select: Box::new(|index| Messages::SelectSegment(index)),
+                        add_segment: Box::new(|| Messages::AddSegment),
+                        remove_segment: Box::new(|| Messages::RemoveSegment),
                         update_start: Box::new(|position| Messages::UpdateStart(position)),
                         update_end: Box::new(|position| Messages::UpdateEnd(position)),
                         update_anywhere: Box::new(|position| Messages::Pressed(position)),
@@ -385,15 +1101,95 @@ fn view(app: &App) -> iced::Element<Messages> {
                         toggle_start: Box::new(|position| Messages::PressedStart(position)),
                         toggle_end: Box::new(|position| Messages::PressedEnd(position)),
                         set_time: Box::new(|position| Messages::SetTime(position)),
+                        set_in: Box::new(|| Messages::SetIn),
+                        set_out: Box::new(|| Messages::SetOut),
                         positional_update: Box::new(|position| Messages::PositionalUpdate(position)),
                         cursor_position: app.cursor_position,
                         pressed_anywhere: app.pressed_anywhere,
                         play_pause: Box::new(|| Messages::PlayPause),
+                        shuttle: Box::new(|direction| Messages::Shuttle(direction)),
+                        change_speed: Box::new(|step| Messages::ChangeSpeed(step)),
+                        toggle_loop: Box::new(|| Messages::ToggleLoop),
+                        open: Box::new(|| Messages::OpenDialog),
 
                         restart: Box::new(|| Messages::RestartStream),
-                        is_processing: app.processing
+                        export: Box::new(|| Messages::Export),
+                        cancel: Box::new(|| Messages::CancelExport),
                     }
                 )
+                .push(
+                    button::Button::new(widget::text("crop"))
+                        .style(toggle_button_style(app, app.cropping || app.crop.is_some() || !app.transform.is_identity()))
+                        .on_press_maybe(app.video_info.is_some().then_some(Messages::ToggleCrop))
+                )
+                .push_maybe(app.cropping.then(|| {
+                    widget::pick_list(Aspect::ALL, Some(app.aspect), Messages::SetAspect)
+                        .style(pick_list_style(app))
+                        .menu_style(menu_style(app))
+                }))
+                // turning and flipping live next to the crop, since the crop is picked on the turned frame
+                .push_maybe(app.cropping.then(|| {
+                    button::Button::new(widget::text("rotate"))
+                        .style(toggle_button_style(app, app.transform.quarter_turns != 0))
+                        .on_press(Messages::Rotate)
+                }))
+                .push_maybe(app.cropping.then(|| {
+                    button::Button::new(widget::text("flip h"))
+                        .style(toggle_button_style(app, app.transform.flip_horizontal))
+                        .on_press(Messages::Flip { horizontal: true })
+                }))
+                .push_maybe(app.cropping.then(|| {
+                    button::Button::new(widget::text("flip v"))
+                        .style(toggle_button_style(app, app.transform.flip_vertical))
+                        .on_press(Messages::Flip { horizontal: false })
+                }))
+                .push_maybe(app.can_stream_copy().then(|| {
+                    button::Button::new(widget::text("lossless"))
+                        .style(toggle_button_style(app, app.stream_copy))
+                        .on_press(Messages::ToggleStreamCopy)
+                }))
+                .push_maybe(stream_copy.then(|| {
+                    // stream copies can only start at a keyframe, so show how far the cut moves because of it
+                    widget::text(if app.keyframes.is_empty() {
+                        "reading keyframes".to_string()
+                    } else {
+                        let segment = &app.segments[app.selected];
+                        let (start, end) = app.stream_copy_cut();
+                        format!(
+                            "{}cuts {:.2}s - {:.2}s, starts {:.2}s earlier",
+                            if app.segments.len() > 1 { format!("segment {}: ", app.selected + 1) } else { String::new() },
+                            start,
+                            end,
+                            segment.start - start
+                        )
+                    })
+                        .color(hex_to_rgba(&app.config.main_color, 0.75))
+                }))
+                .push_maybe((!stream_copy).then(|| {
+                    widget::pick_list(outputs(app), Some(app.output), Messages::SetOutput)
+                        .style(pick_list_style(app))
+                        .menu_style(menu_style(app))
+                }))
+                // what the export gets scaled down to, only shown once the size of the video is known
+                .push_maybe(picture_info.map(|info| {
+                    let heights = heights(app, info);
+                    let selected = heights.iter().find(|choice| choice.value == app.height).copied();
+                    widget::pick_list(heights, selected, |choice| Messages::SetHeight(choice.value))
+                        .style(pick_list_style(app))
+                        .menu_style(menu_style(app))
+                }))
+                .push_maybe(picture_info.map(|info| {
+                    let framerates = framerates(app, info);
+                    let selected = framerates.iter().find(|choice| choice.value == app.fps).copied();
+                    widget::pick_list(framerates, selected, |choice| Messages::SetFps(choice.value))
+                        .style(pick_list_style(app))
+                        .menu_style(menu_style(app))
+                }))
+                .push(
+                    widget::pick_list(target_sizes(app), Some(app.target_size), Messages::SetTargetSize)
+                        .style(pick_list_style(app))
+                        .menu_style(menu_style(app))
+                )
                 .push(
                     button::Button::new(
                         Svg::from_path(&app.trim_icon)
@@ -435,48 +1231,173 @@ fn view(app: &App) -> iced::Element<Messages> {
 
 }
 
-fn update(app: &mut App, message: Messages)  {
+fn button_style(app: &App) -> impl Fn(&Theme, button::Status) -> button::Style + '_ {
+    move |state, theme| {
+        widget::button::Style {
+            background: Some(Background::Color(hex_to_rgba(&app.config.main_color, 0.15))),
+            text_color: hex_to_rgba(&app.config.main_color, 0.75),
+            border: Border::default().rounded(10.0),
+            shadow: Shadow::default(),
+        }
+    }
+}
+
+/// A button that stands out while `active` is on
+fn toggle_button_style(app: &App, active: bool) -> impl Fn(&Theme, button::Status) -> button::Style + '_ {
+    move |state, theme| {
+        widget::button::Style {
+            background: Some(Background::Color(hex_to_rgba(&app.config.main_color, if active { 0.35 } else { 0.15 }))),
+            text_color: hex_to_rgba(&app.config.main_color, if active { 1.0 } else { 0.5 }),
+            border: Border::default().rounded(10.0),
+            shadow: Shadow::default(),
+        }
+    }
+}
+
+fn pick_list_style(app: &App) -> impl Fn(&Theme, widget::pick_list::Status) -> widget::pick_list::Style + '_ {
+    move |theme, status| {
+        widget::pick_list::Style {
+            text_color: hex_to_rgb(&app.config.main_color),
+            placeholder_color: hex_to_rgba(&app.config.main_color, 0.5),
+            handle_color: hex_to_rgba(&app.config.main_color, 0.75),
+            background: Background::Color(hex_to_rgba(&app.config.main_color, 0.15)),
+            border: Border::default().rounded(10.0),
+        }
+    }
+}
+
+/// The dropdown of a pick list
+fn menu_style(app: &App) -> impl Fn(&Theme) -> widget::overlay::menu::Style + '_ {
+    move |theme| {
+        widget::overlay::menu::Style {
+            background: Background::Color(hex_to_rgb(&app.config.hover_background)),
+            border: Border::default().rounded(10.0),
+            text_color: hex_to_rgb(&app.config.main_color),
+            selected_text_color: hex_to_rgb(&app.config.main_color),
+            selected_background: Background::Color(hex_to_rgba(&app.config.main_color, 0.15)),
+        }
+    }
+}
+
+/// The preset formats (plus the one from the config if it isnt one of them), then a gif and a webp,
+/// then every audio format at the bitrate from the config
+fn outputs(app: &App) -> Vec<Output> {
+    let mut formats = Format::PRESETS.to_vec();
+    if !formats.contains(&app.config.format) {
+        formats.insert(0, app.config.format);
+    }
+    formats
+        .into_iter()
+        .map(Output::Video)
+        .chain([AnimationKind::Gif, AnimationKind::Webp].map(|kind| Output::Animation(Animation { kind, ..app.config.animation })))
+        .chain(AudioFormat::ALL.map(|format| Output::Audio(AudioOnly { format, ..app.config.audio_only })))
+        .collect()
+}
+
+/// An entry of the resolution or framerate pick lists, the automatic one shows what it picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Choice {
+    value: Option<u32>,
+    auto: u32,
+    unit: &'static str,
+}
+
+impl std::fmt::Display for Choice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{value}{}", self.unit),
+            None => write!(f, "auto ({}{})", self.auto, self.unit),
+        }
+    }
+}
+
+/// Automatic, the source height (the shorter side) and every step below it
+fn heights(app: &App, info: VideoInfo) -> Vec<Choice> {
+    let auto = app.picture(None, app.fps).unwrap().height;
+    [None, Some(info.short_side())]
+        .into_iter()
+        .chain(export::HEIGHTS.into_iter().filter(|height| *height < info.short_side()).map(Some))
+        .map(|value| Choice { value, auto, unit: "p" })
+        .collect()
+}
+
+/// Automatic, the source framerate and every step below it
+fn framerates(app: &App, info: VideoInfo) -> Vec<Choice> {
+    let auto = app.picture(app.height, None).unwrap().fps;
+    let source = info.fps.round() as u32;
+    [None, Some(source)]
+        .into_iter()
+        .chain(export::FRAMERATES.into_iter().filter(|fps| *fps < source).map(Some))
+        .map(|value| Choice { value, auto, unit: "fps" })
+        .collect()
+}
+
+/// The preset sizes, plus the one from the config if it isnt one of them
+fn target_sizes(app: &App) -> Vec<TargetSize> {
+    let mut sizes = TargetSize::PRESETS.to_vec();
+    if !sizes.contains(&app.config.target_size) {
+        sizes.insert(0, app.config.target_size);
+    }
+    sizes
+}
+
+fn update(app: &mut App, message: Messages) -> Task<Messages> {
     match message {
         Messages::NewFrame => {
             let position = app.video.position();
             app.cursor_position = position.as_secs_f32();
+            if !app.video.paused() && app.cursor_position > app.view_end {
+                // turn the page when playback runs off the zoomed in part of the timeline
+                let span = app.view_end - app.view_start;
+                app.view_start = app.cursor_position.min(app.video_length - span);
+                app.view_end = app.view_start + span;
+            }
+            if let Some(target) = app.loop_target() {
+                app.video.seek(Position::Time(Duration::from_secs_f32(target)), true).unwrap();
+                app.cursor_position = target;
+            }
         },
+        Messages::ToggleLoop => {
+            app.position_loop = !app.position_loop;
+            // the end of the video has to go back to the start too, when the last segment reaches it
+            app.video.set_looping(app.position_loop);
+        }
         Messages::PlayPause => {
             app.video.set_paused(!app.video.paused());
 
         }
         Messages::UpdateStart(position) => {
-            app.mouse_position = position * app.video_length;
-            let time = time::Duration::seconds_f32(app.video_length * position);
+            app.mouse_position = position;
+            let time = time::Duration::seconds_f32(position);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
                 time.whole_seconds() - time.whole_minutes() * 60,
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
-            app.start = position;
-            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), false).unwrap();
+            let position = app.set_start(position);
+            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), true).unwrap();
             app.cursor_position = position;
 
         }
         Messages::TickStart => {
             let position = app.position_value;
-            app.mouse_position = position * app.video_length;
-            let time = time::Duration::seconds_f32(app.video_length * position);
+            app.mouse_position = position;
+            let time = time::Duration::seconds_f32(position);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
                 time.whole_seconds() - time.whole_minutes() * 60,
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
-            app.start = position;
-            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), false).unwrap();
+            let position = app.set_start(position);
+            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), true).unwrap();
             app.cursor_position = position;
             app.moving = false;
         }
         Messages::UpdateEnd(position) => {
-            app.mouse_position = position * app.video_length;
-            let time = time::Duration::seconds_f32(app.video_length * position);
+            app.mouse_position = position;
+            let time = time::Duration::seconds_f32(position);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
@@ -484,8 +1405,8 @@ fn update(app: &mut App, message: Messages)  {
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
 
-            app.end = position;
-            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), false).unwrap();
+            let position = app.set_end(position);
+            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), true).unwrap();
             // app.video.seek(Position::Time(Duration::from_secs_f32(position)), true);
             app.cursor_position = position;
 
@@ -493,8 +1414,8 @@ fn update(app: &mut App, message: Messages)  {
         Messages::TickEnd => {
             let position = app.position_value;
 
-            app.mouse_position = position * app.video_length;
-            let time = time::Duration::seconds_f32(app.video_length * position);
+            app.mouse_position = position;
+            let time = time::Duration::seconds_f32(position);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
@@ -502,8 +1423,8 @@ fn update(app: &mut App, message: Messages)  {
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
 
-            app.end = position;
-            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), false).unwrap();
+            let position = app.set_end(position);
+            app.video.seek(Position::Time(Duration::from_secs_f32((position * 1000.0).round() / 1000.0)), true).unwrap();
             // app.video.seek(Position::Time(Duration::from_secs_f32(position)), true);
             app.cursor_position = position;
             app.moving = false;
@@ -514,6 +1435,160 @@ fn update(app: &mut App, message: Messages)  {
         Messages::PressedEnd(value) => {
             app.pressed_end = value;
         }
+        Messages::SelectSegment(index) => {
+            app.selected = index;
+        }
+        Messages::SetIn => {
+            let position = app.cursor_position;
+            if position > app.segments[app.selected].end {
+                // an in point past the out point pushes the out point as far as it can go, like in other editors
+                app.set_end(app.video_length);
+            }
+            app.set_start(position);
+        }
+        Messages::SetOut => {
+            let position = app.cursor_position;
+            if position < app.segments[app.selected].start {
+                app.set_start(0.0);
+            }
+            app.set_end(position);
+        }
+        Messages::Shuttle(direction) => {
+            if direction == 0 {
+                app.video.set_paused(true);
+                app.set_speed(1.0);
+            } else {
+                let direction = direction as f64;
+                // pressing the same direction again while playing doubles the speed
+                let speed = if !app.video.paused() && app.speed.signum() == direction {
+                    (app.speed * 2.0).clamp(-SPEEDS[SPEEDS.len() - 1], SPEEDS[SPEEDS.len() - 1])
+                } else {
+                    direction
+                };
+                app.set_speed(speed);
+                // a video that cant play backwards stays paused instead of carrying on the other way
+                app.video.set_paused(app.speed != speed);
+            }
+        }
+        Messages::ChangeSpeed(step) => {
+            // steps to the next slower/faster speed, keeping the direction when playing backwards
+            let index = SPEEDS.iter().position(|speed| *speed >= app.speed.abs()).unwrap_or(SPEEDS.len() - 1);
+            let index = (index as i32 + step as i32).clamp(0, SPEEDS.len() as i32 - 1) as usize;
+            app.set_speed(SPEEDS[index] * app.speed.signum());
+        }
+        Messages::SetView(start, end) => {
+            app.view_start = start;
+            app.view_end = end;
+        }
+        Messages::AddSegment => {
+            let position = app.cursor_position;
+            if let Some(index) = app.segments.iter().position(|segment| segment.start < position && position < segment.end) {
+                // splitting a segment at the playhead, so that the part between can be dragged away
+                let end = app.segments[index].end;
+                app.segments[index].end = position;
+                app.segments.insert(index + 1, Segment { start: position, end });
+                app.selected = index + 1;
+            } else {
+                let index = app.segments.iter().position(|segment| segment.start > position).unwrap_or(app.segments.len());
+                let limit = app.segments.get(index).map(|segment| segment.start).unwrap_or(app.video_length);
+                let end = (position + app.video_length * 0.05).min(limit);
+                if end > position {
+                    app.segments.insert(index, Segment { start: position, end });
+                    app.selected = index;
+                }
+            }
+        }
+        Messages::RemoveSegment => {
+            if app.segments.len() > 1 {
+                app.segments.remove(app.selected);
+                app.selected = app.selected.min(app.segments.len() - 1);
+            }
+        }
+        Messages::SetTargetSize(target) => {
+            app.target_size = target;
+        }
+        Messages::SetOutput(output) => {
+            app.output = output;
+        }
+        Messages::ToggleCrop => {
+            app.cropping = !app.cropping;
+        }
+        Messages::SetCrop(crop) => {
+            app.crop = crop;
+        }
+        Messages::SetAspect(aspect) => {
+            app.aspect = aspect;
+            if let Some(info) = app.turned_info() {
+                app.crop = app.crop.map(|crop| crop.fit(aspect, (info.width, info.height)));
+            }
+        }
+        Messages::Rotate => {
+            // the flips happen after the turns, so turning a mirrored frame clockwise is a turn the other way before the mirror
+            let mirrored = app.transform.flip_horizontal != app.transform.flip_vertical;
+            app.transform.quarter_turns = (app.transform.quarter_turns + if mirrored { 3 } else { 1 }) % 4;
+            // the crop turns along with the frame
+            app.crop = app.crop.map(Crop::turned);
+            preview_transform(&app.video, app.transform);
+        }
+        Messages::Flip { horizontal } => {
+            if horizontal {
+                app.transform.flip_horizontal = !app.transform.flip_horizontal;
+            } else {
+                app.transform.flip_vertical = !app.transform.flip_vertical;
+            }
+            app.crop = app.crop.map(|crop| crop.flipped(horizontal));
+            preview_transform(&app.video, app.transform);
+        }
+        Messages::SetHeight(height) => {
+            app.height = height;
+        }
+        Messages::SetFps(fps) => {
+            app.fps = fps;
+        }
+        Messages::ToggleStreamCopy => {
+            app.stream_copy = !app.stream_copy;
+        }
+        Messages::KeyframesLoaded(file, keyframes) if file == app.old_file => {
+            app.keyframes = keyframes;
+        }
+        Messages::ThumbnailsLoaded(file, thumbnails) if file == app.old_file => {
+            app.thumbnails = thumbnails.into_iter().map(|thumbnail| thumbnail.map(widget::image::Handle::from_path)).collect();
+        }
+        Messages::WaveformLoaded(file, waveform) if file == app.old_file => {
+            app.waveform = waveform;
+        }
+        // previews of a file that isnt open anymore
+        Messages::VideoInfoLoaded(file, info) if file == app.old_file => {
+            app.video_info = info;
+        }
+        Messages::KeyframesLoaded(..) | Messages::VideoInfoLoaded(..) | Messages::ThumbnailsLoaded(..) | Messages::WaveformLoaded(..) => {}
+        Messages::OpenDialog => {
+            return Task::future(smol::unblock(|| FileDialog::new().pick_files()))
+                .and_then(|files| Task::done(Messages::Open(files)));
+        }
+        Messages::Open(files) => {
+            // files that are gone (or folders) never make it into the list
+            let files: Vec<PathBuf> = files
+                .into_iter()
+                .filter(|file| match file_size(file) {
+                    Ok(_) => true,
+                    Err(error) => {
+                        notify(app, &format!("{} couldnt be opened: {}", file.display(), error));
+                        false
+                    }
+                })
+                .collect();
+            for file in &files {
+                if !app.files.contains(file) {
+                    app.files.push(file.clone());
+                }
+            }
+            if let Some(file) = files.first() {
+                if *file != app.old_file {
+                    return app.open(file.clone());
+                }
+            }
+        }
         Messages::Pressed(value) => {
             app.pressed_anywhere = value;
         }
@@ -523,39 +1598,39 @@ fn update(app: &mut App, message: Messages)  {
         }
         Messages::SetTime(value) => {
             app.position_value = value;
-            let time = time::Duration::seconds_f32(app.video_length * value);
+            let time = time::Duration::seconds_f32(value);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
                 time.whole_seconds() - time.whole_minutes() * 60,
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
-            app.mouse_position = value * app.video_length;
+            app.mouse_position = value;
 
-            app.video.seek(Position::Time(Duration::from_secs_f32((value * 1000.0).round() / 1000.0)), false).unwrap();
+            app.video.seek(Position::Time(Duration::from_secs_f32((value * 1000.0).round() / 1000.0)), true).unwrap();
             app.cursor_position = value;
 
         }
         Messages::TickTime => {
             let value = app.position_value;
 
-            let time = time::Duration::seconds_f32(app.video_length * value);
+            let time = time::Duration::seconds_f32(value);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
                 time.whole_seconds() - time.whole_minutes() * 60,
                 (time.as_seconds_f32() - time.whole_seconds() as f32) * 1000.0,
             );
-            app.mouse_position = value * app.video_length;
+            app.mouse_position = value;
 
-            app.video.seek(Position::Time(Duration::from_secs_f32((value * 1000.0).round() / 1000.0)), false).unwrap();
+            app.video.seek(Position::Time(Duration::from_secs_f32((value * 1000.0).round() / 1000.0)), true).unwrap();
             app.cursor_position = value;
 
             app.moving = false;
         }
         Messages::MouseMove(value) => {
             app.mouse_position = value;
-            let time = time::Duration::seconds_f32(app.video_length * value);
+            let time = time::Duration::seconds_f32(value);
             app.mouse_content = format!(
                 "{:02}:{:02}.{:03.0}",
                 time.whole_minutes(),
@@ -567,10 +1642,76 @@ fn update(app: &mut App, message: Messages)  {
             app.moving = value;
         }
         Messages::RestartStream => {
+            // the file might have grown (or shrunk) since it was opened, so everything about it is read again
+            let position = app.cursor_position;
+            let paused = app.video.paused();
+            let (video, framerate) = match load_video(&app.old_file, app.transform) {
+                Ok(loaded) => loaded,
+                // the file might be gone, the old player keeps what it has
+                Err(error) => {
+                    notify(app, &format!("{} couldnt be reloaded: {}", app.old_file.display(), error));
+                    return Task::none();
+                }
+            };
+            app.video = video;
+            app.framerate = framerate;
+            app.speed = 1.0;
+            app.video_length = app.video.duration().as_secs_f32();
+            app.video_time = time::Duration::seconds_f32(app.video_length);
+            app.file_size = fs::metadata(&app.old_file).map_or(app.file_size, |metadata| metadata.len());
+            app.video.set_looping(app.position_loop);
+
+            let length = app.video_length;
+            app.segments.retain(|segment| segment.start < length);
+            if app.segments.is_empty() {
+                app.segments.push(Segment { start: 0.0, end: length });
+            }
+            for segment in &mut app.segments {
+                segment.end = segment.end.min(length);
+            }
+            app.selected = app.selected.min(app.segments.len() - 1);
+            let span = (app.view_end - app.view_start).min(length);
+            app.view_start = app.view_start.min(length - span);
+            app.view_end = app.view_start + span;
+
+            app.cursor_position = position.min(length);
+            app.video.seek(Position::Time(Duration::from_secs_f32(app.cursor_position)), true).unwrap();
+            app.video.set_paused(paused);
+            return load_previews(&app.old_file, app.video_length);
         }
         Messages::Export => {
-            app.video.set_paused(true);
-            app.processing = true;
+            // the selection is taken right away, so editing can go on while the dialog is open
+            let file = app.old_file.clone();
+            let settings = app.export_settings();
+            // stream copies keep the container of the original
+            let name = if settings.stream_copy {
+                file.file_name().unwrap().to_str().unwrap().to_string()
+            } else {
+                file.with_extension(settings.output.extension()).file_name().unwrap().to_str().unwrap().to_string()
+            };
+            let dialog = FileDialog::new()
+                .set_file_name(name)
+                .set_directory(file.parent().unwrap());
+            let config = app.config.clone();
+            return Task::future(smol::unblock(move || dialog.save_file()))
+                .and_then(move |mut output| {
+                    let mut settings = settings.clone();
+                    let extension = output.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_lowercase();
+                    // the name might have been changed to end in something else, which then picks what gets exported the same
+                    // way it does for the cli. names that dont say anything get the extension of what was picked in the editor
+                    if !settings.stream_copy && extension != settings.output.extension() {
+                        match output_for(&output, &config) {
+                            Ok(kind) => settings.output = kind,
+                            Err(_) => output = output.with_extension(settings.output.extension()),
+                        }
+                    }
+                    Task::done(Messages::Queue(Job {
+                        id: 0,
+                        file: file.clone(),
+                        output,
+                        settings,
+                    }))
+                });
             // let _ = FFmpeg::auto_download();
             // if let Some((handle, mut progress)) = FFmpeg::auto_download() {
             //     handle.unwrap().unwrap();
@@ -578,30 +1719,55 @@ fn update(app: &mut App, message: Messages)  {
             //     println!("FFmpeg is downloaded, using existing installation");
             // }
         }
-        Messages::ProcessingDone => {
-            app.processing = false;
-            Notification::new()
-                .summary("sickle")
-                .body("Video clip has been processed")
-                .icon(if app.is_flatpak { "com.github.vnuxa.sickle" } else { "sickle" })
-                .show();
+        Messages::ExportProgress(id, progress) => {
+            // a cancelled job can still send some before it notices
+            if app.queue.first().is_some_and(|job| job.id == id) {
+                app.progress = Some(progress);
+            }
+        }
+        Messages::Queue(mut job) => {
+            job.id = app.next_job;
+            app.next_job += 1;
+            app.queue.push(job);
+        }
+        Messages::Unqueue(id) => {
+            if app.queue.first().is_some_and(|job| job.id == id) {
+                return update(app, Messages::CancelExport);
+            }
+            app.queue.retain(|job| job.id != id);
+        }
+        Messages::CancelExport => {
+            if !app.queue.is_empty() {
+                // the export thread notices this, kills ffmpeg and cleans up after it
+                app.cancel.store(true, Ordering::Relaxed);
+                app.finish_job();
+            }
+        }
+        // the job might have been cancelled (and taken off the queue) while it was finishing
+        Messages::ExportFailed(id, reason) if app.queue.first().is_some_and(|job| job.id == id) => {
+            let job = app.finish_job();
+            let name = job.file.file_name().unwrap().to_string_lossy().to_string();
+            notify(app, &format!("{} couldnt be exported: {}", name, reason));
+        }
+        Messages::ProcessingDone(id, size) if app.queue.first().is_some_and(|job| job.id == id) => {
+            let job = app.finish_job();
+            let name = job.file.file_name().unwrap().to_string_lossy().to_string();
+            let body = match job.settings.target {
+                TargetSize::Limit(target) if size > target => format!(
+                    "{} has been processed, but at {} it is still above {}",
+                    name,
+                    export::format_size(size),
+                    job.settings.target
+                ),
+                _ => format!("{} has been processed ({})", name, export::format_size(size)),
+            };
+            notify(app, &body);
             if let Some(audio) = &app.config.audio {
 
                 let stream_handle = rodio::OutputStreamBuilder::open_default_stream().unwrap();
                 let mixer = stream_handle.mixer();
 
-                let mut path = PathBuf::from("/");
-                for text in audio.split("/") {
-                    if text == "~" {
-                        path.push(home_dir().unwrap().to_str().unwrap());
-                    } else {
-                        path.push(text);
-                    }
-                }
-
-
-                let file = File::open(path).unwrap();
-                println!("file is {:?}", file);
+                let file = File::open(expand_path(audio)).unwrap();
                 // let sink = rodio::play(mixer, BufReader::new(file.unwrap())).unwrap();
 
                 let sink = rodio::Sink::connect_new(stream_handle.mixer());
@@ -614,9 +1780,10 @@ fn update(app: &mut App, message: Messages)  {
                 sink.sleep_until_end();
             }
         }
+        Messages::ExportFailed(..) | Messages::ProcessingDone(..) => {}
 
     }
-
+    Task::none()
 }
 
 fn subscription(state: &App) -> Subscription<Messages> {
@@ -631,8 +1798,13 @@ fn subscription(state: &App) -> Subscription<Messages> {
         }
     }
 
-    if state.processing {
-        subscriptions.push(ffmpeg_process(state.old_file.clone(), state.start.clone(), state.end.clone()));
+    subscriptions.push(iced::event::listen_with(|event, _status, _window| match event {
+        iced::Event::Window(iced::window::Event::FileDropped(file)) => Some(Messages::Open(vec![file])),
+        _ => None,
+    }));
+
+    if let Some(job) = state.queue.first() {
+        subscriptions.push(ffmpeg_process(job.clone(), state.cancel.clone()));
     }
 
     if subscriptions.len() > 0 {
@@ -641,147 +1813,44 @@ fn subscription(state: &App) -> Subscription<Messages> {
     Subscription::none()
 }
 
-fn ffmpeg_process(old_file: PathBuf, start: f32, end: f32) -> Subscription<Messages> {
+fn ffmpeg_process(job: Job, cancel: Arc<AtomicBool>) -> Subscription<Messages> {
+    // every job gets its own id, so finishing one starts the next one in the queue
+    let id = job.id;
     Subscription::run_with_id(
-        5,
+        id,
         iced::stream::channel(100, move |mut output| async move {
-            let file = FileDialog::new()
-                .set_file_name(old_file.file_name().unwrap().to_str().unwrap())
-                .set_directory(old_file.parent().unwrap())
-                .save_file();
-
-            // println!("the thign: {:?}", unsafe { make_static_str(&app_arc.start.to_string()) });
-            if let Some(file) = file {
-                // println!("start is {:?}",
-                //     string_to_static_str(app_arc.start.to_string())
-                // );
-                let mut ffmpeg = FFmpeg::new()
-                    .stderr(std::process::Stdio::inherit())
-                    .input_with_file(old_file.clone()).done()
-                    .args([
-                        "-ss",
-                        string_to_static_str(start.to_string())
-                    ])
-                    .args([
-                        "-t",
-                        string_to_static_str((end - start).to_string())
-                    ]);
-
-                let size = old_file.metadata().unwrap().size();
-                // if old file is already bigger than 8 mb, try using some compression techniques
-                if size > 10000000 {
-                    let video_bitrate = ((1400.0) / ((end - start) / 60.0)) * 0.93;
-                    let audio_bitrate = video_bitrate * 0.1;
-                    // let audio_bitrate = (( 318000.0 / ( 1.0 + std::f32::consts::E.powf(-0.0000014 * video_bitrate * 60.0) ) ) - 154000.0) / 2.0;
-                    println!("VIDEO BITRATE SHOULD BE {:?}", video_bitrate);
-                    println!("AUDIO BTIRATE SHOULD BE {:?} which would make video bitrate: {:?}", audio_bitrate, video_bitrate - audio_bitrate);
-                    // app_arc.old_file.metadata().unwrap().audi
-
-                    let mut ffmpeg_2 = FFmpeg::new()
-                        .stderr(std::process::Stdio::inherit())
-                        .input_with_file(old_file.clone()).done()
-                        .args([
-                            "-ss",
-                            string_to_static_str(start.to_string())
-                        ])
-                        .args([
-                            "-t",
-                            string_to_static_str((end - start).to_string())
-                        ])
-                        .arg("-an")
-                        // .output_as_file(file.clone())
-                        .args([
-                            "-c:v",
-                            "libx264"
-                        ])
-                        .args([
-                            "-r",
-                            "30"
-                        ])
-                        .args([
-                            "-b:v",
-                            string_to_static_str(format!("{:.0}k", video_bitrate - audio_bitrate))
-
-                        ])
-                        .args([
-                            "-preset",
-                            "veryslow"
-                        ])
-                        .args([
-                            "-r",
-                            "30"
-                        ])
-                        .args([
-                            "-pass",
-                            "1"
-                        ])
-                        // .args([
-                        //     "-passlogfile",
-                        //     "/tmp/mydummy"
-                        // ])
-                        .args([
-                            "-f",
-                            "rawvideo"
-                        ])
-                        .inspect_args(|args| {
-                            dbg!(args);
-                        });
-                    ;
-                    // ff
-                    // println!("builder is {:?} ", ffmpeg_2.inspect_args(f));
-
-                    ffmpeg_2.start().unwrap().wait().unwrap();
-                    println!("now first done!");
-                    ffmpeg = ffmpeg
-                        .args([
-                            "-c:a",
-                            "aac"
-                        ])
-                        .args([
-                            "-aac_coder",
-                            "twoloop"
-                        ])
-                        .args([
-                            "-b:a",
-                            string_to_static_str(format!("{:.0}k", audio_bitrate))
-                        ])
-                        .args([
-                            "-c:v",
-                            "libx264"
-                        ])
-                        .args([
-                            "-b:v",
-                            string_to_static_str(format!("{:.0}k", video_bitrate - audio_bitrate))
-                        ])
-                        .args([
-                            "-preset",
-                            "veryslow"
-                        ])
-                        .args([
-                            "-pass",
-                            "1"
-                        ]);
-                    // .args([
-                    //     "-passlogfile",
-                    //     "/tmp/mydummy"
-                    // ]);
-
-                }
-
-                ffmpeg = ffmpeg.output_as_file(file.clone()).done();
-                println!("file size is {:?}", old_file.metadata().unwrap().size());
-                // println!("file is of size {:?}", ::new(source).into_iter().map(|item| item.metadata().unwrap().len()));
-                // if  {
-                //
-                // }
-
-
-                ffmpeg.start().unwrap().wait().unwrap();
-                output
-                    .try_send(Messages::ProcessingDone)
-                    .expect("failed to send ffmpeg processing done event");
-            }
+            let mut progress = output.clone();
+            // ffmpeg blocks, so it runs on its own thread to let the progress through while it works
+            let size = smol::unblock(move || {
+                export::export(job.file, job.output, job.settings, &cancel, |update| {
+                    let _ = progress.try_send(Messages::ExportProgress(id, update));
+                })
+            }).await;
+            let message = match size {
+                Ok(size) => Messages::ProcessingDone(id, size),
+                Err(ExportError::Failed(reason)) => Messages::ExportFailed(id, reason),
+                // the job is already off the queue
+                Err(ExportError::Cancelled) => return,
+            };
+            output
+                .try_send(message)
+                .expect("failed to send ffmpeg processing done event");
 
         })
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("1:02:03.5"), Ok(3723.5));
+        assert_eq!(parse_timestamp("02:03.5"), Ok(123.5));
+        assert_eq!(parse_timestamp("12.5"), Ok(12.5));
+        assert!(parse_timestamp("-1").is_err());
+        assert!(parse_timestamp("1:2:3:4").is_err());
+        assert!(parse_timestamp("soon").is_err());
+    }
+}
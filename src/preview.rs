@@ -0,0 +1,148 @@
+use std::env::home_dir;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use essi_ffmpeg::FFmpeg;
+
+use crate::string_to_static_str;
+
+/// how many thumbnails the filmstrip behind the timeline is made of
+pub const THUMBNAIL_COUNT: usize = 32;
+
+/// how many peaks of the waveform there are for every second of audio
+pub const PEAKS_PER_SECOND: usize = 25;
+
+/// the audio only gets decoded at this rate, plenty for drawing it
+const WAVEFORM_SAMPLE_RATE: usize = 8000;
+
+/// Where the previews of `file` are cached, a different folder is used whenever the file gets modified.
+/// The folder is named after the path and then the version of the file, so the older versions can be found
+fn cache_dir(file: &PathBuf) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    file.hash(&mut hasher);
+    let path_hash = hasher.finish();
+    file.metadata().and_then(|metadata| metadata.modified()).ok().hash(&mut hasher);
+
+    let mut path = home_dir().unwrap();
+    path.push(".cache/");
+    path.push("sickle/");
+    path.push(format!("{:016x}-{:016x}", path_hash, hasher.finish()));
+    path
+}
+
+/// Removes the previews of older versions of the file `cache` is for, they are never used again once it got modified
+fn remove_old_caches(cache: &PathBuf) {
+    let name = cache.file_name().unwrap().to_string_lossy().to_string();
+    let prefix = format!("{}-", name.split('-').next().unwrap());
+    let Ok(entries) = fs::read_dir(cache.parent().unwrap()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if entry_name.starts_with(&prefix) && entry_name != name {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Grabs evenly spaced frames of `file` as images, reusing the cached ones if they are there already.
+/// Frames that couldnt be grabbed are `None`, so every thumbnail stays at its own place in the video
+pub fn thumbnails(file: &PathBuf, duration: f32) -> Vec<Option<PathBuf>> {
+    let cache = cache_dir(file);
+    if !cache.exists() {
+        remove_old_caches(&cache);
+    }
+    let dir = cache.join("thumbnails");
+    if fs::create_dir_all(&dir).is_err() {
+        return Vec::new();
+    }
+
+    (0..THUMBNAIL_COUNT)
+        .map(|index| {
+            let thumbnail = dir.join(format!("{index:02}.jpg"));
+            if thumbnail.exists() {
+                return Some(thumbnail);
+            }
+
+            // written under another name first so a half written thumbnail never ends up in the cache
+            let partial = dir.join(format!("{index:02}.part.jpg"));
+            let time = duration * (index as f32 + 0.5) / THUMBNAIL_COUNT as f32;
+            FFmpeg::new()
+                .stderr(Stdio::null())
+                .arg("-y")
+                .args([
+                    "-ss",
+                    string_to_static_str(time.to_string())
+                ])
+                .input_with_file(file.clone()).done()
+                .args([
+                    "-frames:v",
+                    "1"
+                ])
+                .args([
+                    "-vf",
+                    "scale=-2:120"
+                ])
+                .args([
+                    "-q:v",
+                    "4"
+                ])
+                .output_as_file(partial.clone()).done()
+                .start().ok()?
+                .wait().ok()?;
+
+            fs::rename(&partial, &thumbnail).ok()?;
+            Some(thumbnail)
+        })
+        .collect()
+}
+
+/// Decodes the first audio track of `file` into the loudest sample (from 0 to 1) of every [`PEAKS_PER_SECOND`]th of a second.
+/// Videos without audio get an empty waveform
+pub fn waveform(file: &PathBuf) -> Vec<f32> {
+    let Ok(mut child) = FFmpeg::new()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .input_with_file(file.clone()).done()
+        .args([
+            "-map",
+            "0:a:0",
+            "-ac",
+            "1",
+            "-ar",
+            string_to_static_str(WAVEFORM_SAMPLE_RATE.to_string()),
+            "-f",
+            "s16le"
+        ])
+        .output_as_file(PathBuf::from("pipe:1")).done()
+        .start()
+    else {
+        return Vec::new();
+    };
+
+    let samples_per_peak = WAVEFORM_SAMPLE_RATE / PEAKS_PER_SECOND;
+    let mut peaks = Vec::new();
+    let mut peak: i32 = 0;
+    let mut count = 0;
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut sample = [0; 2];
+    while stdout.read_exact(&mut sample).is_ok() {
+        peak = peak.max((i16::from_le_bytes(sample) as i32).abs());
+        count += 1;
+        if count == samples_per_peak {
+            peaks.push(peak as f32 / i16::MAX as f32);
+            peak = 0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        peaks.push(peak as f32 / i16::MAX as f32);
+    }
+
+    let _ = child.wait();
+    peaks
+}
@@ -8,8 +8,6 @@ use iced::alignment::Horizontal;
 use iced::alignment::Vertical;
 use iced::border::Radius;
 use iced::keyboard;
-use iced::keyboard::key::Named;
-use iced::keyboard::Key;
 use iced::Background;
 use iced::Border;
 use iced::Color;
@@ -25,18 +23,51 @@ use iced::Theme;
 use iced::advanced::renderer;
 use iced::Vector;
 use iced::advanced::graphics::core;
+use iced::advanced::image;
+use iced::advanced::widget::tree;
 use iced::advanced::text::Renderer as _;
 
 
+use std::sync::Arc;
+
+use crate::keys::Action;
+use crate::preview::PEAKS_PER_SECOND;
 use crate::Config;
 use crate::Messages;
 
+/// the timeline never zooms in closer than this many seconds across its whole width
+const MIN_VIEW: f32 = 1.0;
+
+/// A part of the video that is kept on export
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Segment {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
 
 pub struct Timeline<Message> {
     pub duration: f32,
     pub cursor_position: f32,
-    pub start: f32,
-    pub end: f32,
+    /// frames per second of the video, the arrow keys step through it one frame at a time
+    pub framerate: f32,
+    pub segments: Vec<Segment>,
+    pub selected: usize,
+    /// sorted timestamps of the keyframes, drawn as ticks along the bottom
+    pub keyframes: Vec<f32>,
+    /// evenly spaced frames of the video, drawn behind everything as a filmstrip. the ones that couldnt be grabbed are `None`
+    pub thumbnails: Vec<Option<image::Handle>>,
+    /// the peaks of the audio, see [`crate::preview::waveform`]
+    pub waveform: Arc<Vec<f32>>,
+    /// the part of the video that is spread across the width of the timeline, in seconds
+    pub view_start: f32,
+    pub view_end: f32,
+    pub set_view: Box<dyn Fn(f32, f32) -> Message>,
     pub pressed_start:  bool,
     pub pressed_end:  bool,
     pub pressed_anywhere:  bool,
@@ -48,16 +79,31 @@ pub struct Timeline<Message> {
     pub toggle_start: Box<dyn Fn(bool) -> Message>,
     pub toggle_end: Box<dyn Fn(bool) -> Message>,
 
+    pub select: Box<dyn Fn(usize) -> Message>,
+    pub add_segment: Box<dyn Fn() -> Message>,
+    pub remove_segment: Box<dyn Fn() -> Message>,
+
     pub set_time: Box<dyn Fn(f32) -> Message>,
+    /// moves the start/end of the selected segment to the playhead
+    pub set_in: Box<dyn Fn() -> Message>,
+    pub set_out: Box<dyn Fn() -> Message>,
     pub positional_update: Box<dyn Fn(f32) -> Message>,
     pub update_anywhere: Box<dyn Fn(bool) -> Message>,
 
     pub play_pause: Box<dyn Fn() -> Message>,
+    /// plays backwards (-1), pauses (0) or plays forwards (1), repeating a direction speeds it up
+    pub shuttle: Box<dyn Fn(i8) -> Message>,
+    /// steps the playback speed down (-1) or up (1)
+    pub change_speed: Box<dyn Fn(i8) -> Message>,
+    pub toggle_loop: Box<dyn Fn() -> Message>,
+    pub open: Box<dyn Fn() -> Message>,
     pub restart: Box<dyn Fn() -> Message>,
+    pub export: Box<dyn Fn() -> Message>,
     pub mouse: f32,
     pub mouse_content: String,
     pub mouse_move: Box<dyn Fn(f32) -> Message>,
-    pub is_processing: bool,
+    /// stops the export that is running
+    pub cancel: Box<dyn Fn() -> Message>,
 }
 
 pub fn hex_to_rgb(hex: &str) -> Color {
@@ -79,7 +125,84 @@ pub fn hex_to_rgba(hex: &str, alpha: f32) -> Color {
     Color::from_rgba8(r, g, b, alpha)
 }
 
-impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = iced::Font>> Widget<Message, Theme, Renderer> for Timeline<Message>{
+/// Modifiers have to be remembered since scroll events dont come with them
+#[derive(Default)]
+struct State {
+    modifiers: keyboard::Modifiers,
+}
+
+impl<Message> Timeline<Message> {
+    /// Where `time` is drawn on the timeline, depends on how far it is zoomed in and scrolled
+    fn time_to_x(&self, bounds: Rectangle, time: f32) -> f32 {
+        bounds.x + bounds.width * (time - self.view_start) / (self.view_end - self.view_start)
+    }
+
+    /// The timestamp under `x`, anything outside of the timeline counts as its edge
+    fn x_to_time(&self, bounds: Rectangle, x: f32) -> f32 {
+        let x = (x - bounds.x).clamp(0.0, bounds.width);
+        self.view_start + (self.view_end - self.view_start) * x / bounds.width
+    }
+
+    /// Shows `span` seconds starting at `start`, without going past either end of the video
+    fn publish_view(&self, shell: &mut iced::advanced::Shell<'_, Message>, start: f32, span: f32) {
+        let start = start.clamp(0.0, (self.duration - span).max(0.0));
+        shell.publish((self.set_view)(start, start + span));
+    }
+
+    /// Zooms in (or out when `factor` is above 1) while keeping `anchor` at the same spot
+    fn zoom(&self, shell: &mut iced::advanced::Shell<'_, Message>, anchor: f32, factor: f32) {
+        let span = self.view_end - self.view_start;
+        let new_span = (span * factor).clamp(MIN_VIEW.min(self.duration), self.duration);
+        let ratio = (anchor - self.view_start) / span;
+        self.publish_view(shell, anchor - new_span * ratio, new_span);
+    }
+
+    /// Where the handle being held is, or the playhead if none are
+    fn active_position(&self) -> f32 {
+        let segment = self.segments[self.selected];
+        if self.pressed_end {
+            segment.end
+        } else if self.pressed_start {
+            segment.start
+        } else {
+            self.cursor_position
+        }
+    }
+
+    /// Moves the handle being held, or the playhead if none are
+    fn move_active(&self, shell: &mut iced::advanced::Shell<'_, Message>, position: f32) {
+        if self.pressed_end {
+            shell.publish((self.update_end)(position));
+        } else if self.pressed_start {
+            shell.publish((self.update_start)(position));
+        } else {
+            shell.publish((self.set_time)(position));
+        }
+    }
+
+    /// The loudest peak of the waveform between `from` and `to` seconds
+    fn peak(&self, from: f32, to: f32) -> f32 {
+        let len = self.waveform.len();
+        let from = ((from * PEAKS_PER_SECOND as f32) as usize).min(len);
+        let to = ((to * PEAKS_PER_SECOND as f32).ceil() as usize).clamp(from, len);
+        self.waveform[from..to].iter().copied().fold(0.0, f32::max)
+    }
+
+    /// The frame `frames` frames away from the one at `time`
+    fn step(&self, time: f32, frames: f32) -> f32 {
+        (((time * self.framerate).round() + frames) / self.framerate).clamp(0.0, self.duration)
+    }
+
+    fn previous_keyframe(&self, time: f32) -> Option<f32> {
+        self.keyframes.iter().copied().filter(|keyframe| *keyframe < time - 0.001).last()
+    }
+
+    fn next_keyframe(&self, time: f32) -> Option<f32> {
+        self.keyframes.iter().copied().find(|keyframe| *keyframe > time + 0.001)
+    }
+}
+
+impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = iced::Font> + image::Renderer<Handle = image::Handle>> Widget<Message, Theme, Renderer> for Timeline<Message>{
     fn draw(
             &self,
             tree: &iced::advanced::widget::Tree,
@@ -107,74 +230,155 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
             }
         }, Background::Color(hex_to_rgb(&self.config.background_color)));
 
-        let start_portion = view_size.width / (self.duration / self.start);
-        let end_portion = view_size.width / (self.duration / self.end);
-        let mut handle_start_position = view_position;
-        handle_start_position.x = view_position.x + start_portion + 2.5;
-        let mut handle_end_position = view_position;
-        handle_end_position.x = view_position.x + end_portion - 9.5;
-        handle_end_position.y += 2.0;
-        handle_start_position.y += 2.0;
-
-        let timeline = renderer.fill_quad(renderer::Quad {
-            bounds: Rectangle::new(handle_start_position, Size { width: end_portion - start_portion - 4.5, height: 60.0 }),
-            border: Border::default(),
-            shadow: Shadow::default()
-        },
-            // Background::Color(Color::from_rgba8(255, 255, 255, 0.25))
-            Background::Color(
-                hex_to_rgba(&self.config.timeline_color, 0.15)
-                // Color::from_rgba8(130, 159, 98, 0.15)
-
-            )
-
-
-        );
-
-        let handle_start = renderer.fill_quad(renderer::Quad {
-            bounds: Rectangle::new(handle_start_position, Size { width: 7.0, height: 56.0 }),
-            border: Border::default()
-                .color(Color::from_rgb(0.0, 0.0, 0.0))
-                .width(1.0),
-            shadow: Shadow::default()
-
-        },
-            hex_to_rgb(&self.config.timeline_color)
-            // Background::Color(Color::from_rgba8(130, 159, 98, 1.0))
-
-        );
-
-        let handle_end = renderer.fill_quad(renderer::Quad {
-            bounds: Rectangle::new(handle_end_position, Size { width: 7.0, height: 56.0 }),
-            border: Border::default()
-                .color(Color::from_rgb(0.0, 0.0, 0.0))
-                .width(1.0),
-            shadow: Shadow::default()
-
-        },
-            hex_to_rgb(&self.config.timeline_color),
-            // Background::Color(Color::from_rgba8(130, 159, 98, 1.0))
-
-        );
-
-        let mut cursor_position = view_position;
-        cursor_position.x = view_position.x +  view_size.width / (self.duration / self.cursor_position);
-        let cursor_thing = renderer.fill_quad(renderer::Quad {
-            bounds: Rectangle::new(cursor_position, Size { width: 3.0, height: 60.0 }),
-            border: Border::default(),
-                // .color(Color::from_rgb(0.0, 0.0, 0.0))
-                // .width(1.0),
-            shadow: Shadow::default()
-
-        },
-            hex_to_rgba(&self.config.main_color, 0.75)
-            // Background::Color(Color::from_rgba8(178, 135, 161, 0.75))
-
-        );
+        // everything along the timeline is clipped to it, since zooming in pushes most of it out of view
+        renderer.with_layer(view_size, |renderer| {
+            if let Some(first) = self.thumbnails.iter().flatten().next() {
+                // every cell of the filmstrip keeps the aspect ratio of the video, and shows the thumbnail closest to its middle.
+                // cells are lined up with the video and not the widget, so they move along when scrolling
+                let size = renderer.measure_image(first);
+                let cell_width = if size.height > 0 { 60.0 * size.width as f32 / size.height as f32 } else { 60.0 * 16.0 / 9.0 };
+                let cell_span = (self.view_end - self.view_start) * cell_width / view_size.width;
+
+                let mut cell = (self.view_start / cell_span).floor();
+                while cell * cell_span < self.view_end {
+                    let middle = (cell + 0.5) * cell_span / self.duration;
+                    let index = ((middle * self.thumbnails.len() as f32) as usize).min(self.thumbnails.len() - 1);
+                    // a missing thumbnail just leaves its cell empty
+                    if let Some(thumbnail) = &self.thumbnails[index] {
+                        renderer.draw_image(
+                            image::Image::new(thumbnail.clone()).opacity(0.35),
+                            Rectangle::new(
+                                Point { x: self.time_to_x(view_size, cell * cell_span), y: view_position.y },
+                                Size { width: cell_width, height: 60.0 }
+                            ),
+                        );
+                    }
+                    cell += 1.0;
+                }
+            }
+
+            if !self.waveform.is_empty() {
+                // one bar every other pixel, mirrored around the middle of the timeline
+                let mut x = view_position.x;
+                while x < view_position.x + view_size.width {
+                    let peak = self.peak(self.x_to_time(view_size, x), self.x_to_time(view_size, x + 2.0));
+                    let height = peak * 50.0;
+                    renderer.fill_quad(renderer::Quad {
+                        bounds: Rectangle::new(
+                            Point { x, y: view_position.y + 30.0 - height / 2.0 },
+                            Size { width: 1.0, height }
+                        ),
+                        border: Border::default(),
+                        shadow: Shadow::default()
+                    },
+                        hex_to_rgba(&self.config.main_color, 0.3)
+                    );
+                    x += 2.0;
+                }
+            }
+
+            // ticks closer than a couple pixels to the last one are skipped, long videos have thousands of keyframes
+            let mut last_tick = f32::NEG_INFINITY;
+            for keyframe in &self.keyframes {
+                if *keyframe < self.view_start || *keyframe > self.view_end {
+                    continue;
+                }
+                let x = self.time_to_x(view_size, *keyframe);
+                if x - last_tick < 3.0 {
+                    continue;
+                }
+                last_tick = x;
+
+                renderer.fill_quad(renderer::Quad {
+                    bounds: Rectangle::new(Point { x, y: view_position.y + 48.0 }, Size { width: 1.0, height: 10.0 }),
+                    border: Border::default(),
+                    shadow: Shadow::default()
+                },
+                    hex_to_rgba(&self.config.main_color, 0.35)
+                );
+            }
+
+            for (index, segment) in self.segments.iter().enumerate() {
+                // segments that arent being edited are drawn dimmer
+                let alpha = if index == self.selected { 1.0 } else { 0.5 };
+                let start_portion = self.time_to_x(view_size, segment.start) - view_position.x;
+                let end_portion = self.time_to_x(view_size, segment.end) - view_position.x;
+                let mut handle_start_position = view_position;
+                handle_start_position.x = view_position.x + start_portion + 2.5;
+                let mut handle_end_position = view_position;
+                handle_end_position.x = view_position.x + end_portion - 9.5;
+                handle_end_position.y += 2.0;
+                handle_start_position.y += 2.0;
+
+                let timeline = renderer.fill_quad(renderer::Quad {
+                    bounds: Rectangle::new(handle_start_position, Size { width: end_portion - start_portion - 4.5, height: 60.0 }),
+                    border: Border::default(),
+                    shadow: Shadow::default()
+                },
+                    Background::Color(
+                        hex_to_rgba(&self.config.timeline_color, 0.15 * alpha)
+                    )
+                );
+
+                let handle_start = renderer.fill_quad(renderer::Quad {
+                    bounds: Rectangle::new(handle_start_position, Size { width: 7.0, height: 56.0 }),
+                    border: Border::default()
+                        .color(Color::from_rgb(0.0, 0.0, 0.0))
+                        .width(1.0),
+                    shadow: Shadow::default()
+
+                },
+                    hex_to_rgba(&self.config.timeline_color, alpha)
+                );
+
+                let handle_end = renderer.fill_quad(renderer::Quad {
+                    bounds: Rectangle::new(handle_end_position, Size { width: 7.0, height: 56.0 }),
+                    border: Border::default()
+                        .color(Color::from_rgb(0.0, 0.0, 0.0))
+                        .width(1.0),
+                    shadow: Shadow::default()
+
+                },
+                    hex_to_rgba(&self.config.timeline_color, alpha),
+                );
+            }
+
+            let mut cursor_position = view_position;
+            cursor_position.x = self.time_to_x(view_size, self.cursor_position);
+            let cursor_thing = renderer.fill_quad(renderer::Quad {
+                bounds: Rectangle::new(cursor_position, Size { width: 3.0, height: 60.0 }),
+                border: Border::default(),
+                    // .color(Color::from_rgb(0.0, 0.0, 0.0))
+                    // .width(1.0),
+                shadow: Shadow::default()
+
+            },
+                hex_to_rgba(&self.config.main_color, 0.75)
+                // Background::Color(Color::from_rgba8(178, 135, 161, 0.75))
+
+            );
+
+            if self.view_end - self.view_start < self.duration {
+                // a small scrollbar along the top, showing which part of the video is visible
+                renderer.fill_quad(renderer::Quad {
+                    bounds: Rectangle::new(
+                        Point {
+                            x: view_position.x + view_size.width * (self.view_start / self.duration),
+                            y: view_position.y + 2.5
+                        },
+                        Size { width: view_size.width * ((self.view_end - self.view_start) / self.duration), height: 3.0 }
+                    ),
+                    border: Border::default().rounded(1.5),
+                    shadow: Shadow::default()
+                },
+                    hex_to_rgba(&self.config.main_color, 0.5)
+                );
+            }
+        });
 
         if cursor.is_over(view_size) {
             let mut mouse_position = view_position;
-            mouse_position.x = view_position.x +  view_size.width * (self.mouse) - 60.0;
+            mouse_position.x = self.time_to_x(view_size, self.mouse) - 60.0;
             mouse_position.y -= 40.0;
 
             let bounds = Rectangle::new(mouse_position, Size { width: 120.0, height: 35.0 });
@@ -217,64 +421,15 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
         }
 
 
-        if self.is_processing {
-            let bounds = Rectangle::new(Point { x: 0.0, y: 0.0 }, Size { width: viewport.width, height: viewport.height });
-            renderer.with_layer(bounds, |renderer| {
-                renderer.fill_quad(renderer::Quad {
-                    // bounds: Rectangle::new(Point { x: 0.0, y: 0.0 }, Size { width: viewport.width, height: viewport.height }),
-                    bounds: *viewport,
-                    border: Border::default(),
-                    shadow: Shadow::default()
-                },
-                    // Background::Color(Color::from_rgba8(255, 255, 255, 0.25))
-                    Background::Color(
-                        hex_to_rgba(&self.config.hover_background, 0.75)
-                        // Color::from_rgba8(255, 255, 255, 1.0)
-                    )
-                );
-                renderer.fill_quad(renderer::Quad {
-                    // bounds: Rectangle::new(Point { x: 0.0, y: 0.0 }, Size { width: viewport.width, height: viewport.height }),
-                    bounds: Rectangle::new(
-                        Point { x: (viewport.width / 2.0) - 105.0, y: (viewport.height / 2.0) - 30.0 },
-                        Size { width: 210.0, height: 60.0 }
-                    ),
-                    border: Border::default().rounded(10.0),
-                    shadow: Shadow::default()
-                },
-                    // Background::Color(Color::from_rgba8(255, 255, 255, 0.25))
-                    Background::Color(
-                        hex_to_rgba(&self.config.hover_background, 1.0)
-                        // Color::from_rgba8(255, 255, 255, 1.0)
-                    )
-                );
-                // let bounds = Rectangle::new(
-                //     Point { x: (viewport.width / 2.0) - 210.0, y: (viewport.height / 2.0) - 30.0 },
-                //     Size { width: 420.0, height: 60.0 }
-                // );
-                let text = renderer.fill_text(
-                    Text {
-                        wrapping: core::text::Wrapping::None,
-                        shaping: core::text::Shaping::Basic,
-                        horizontal_alignment: Horizontal::Center,
-                        vertical_alignment: Vertical::Center,
-                        font: Font::default(),
-                        size: iced::Pixels(25.0),
-                        line_height: core::text::LineHeight::Absolute(iced::Pixels(10.0)),
-                        bounds: Size { width: 360.0, height: 50.0 },
-                        content: "Processing".to_string(),
-                    },
-                    Point { x: (viewport.width / 2.0), y: (viewport.height / 2.0) },
-                    // Color::from_rgba8(178, 135, 161, 1.0),
-                    hex_to_rgb(&self.config.main_color),
-                    bounds,
-
-                );
-            });
-
-
-        }
+    }
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
 
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
     }
+
     fn size(&self) -> iced::Size<Length> {
         iced::Size { width: Length::Fill, height: Length::Fixed(60.0) }
     }
@@ -311,31 +466,31 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
             return mouse::Interaction::ResizingHorizontally
         }
 
-        let start_portion = view_size.width / (self.duration / self.start);
-        let end_portion = view_size.width / (self.duration / self.end);
-
-        let mut handle_start_position = view_position.x;
-        handle_start_position += start_portion;
-
-        let mut handle_end_position = view_position.x;
-        handle_end_position += end_portion - 7.0;
-
-        if cursor.is_over(Rectangle {
-            x: handle_start_position - 11.0,
-            y: view_position.y,
-            width: 18.0,
-            height: 60.0
-        }) {
-            return mouse::Interaction::Grab;
-        } else if cursor.is_over(Rectangle {
-            x: handle_end_position - 11.0,
-            y: view_position.y,
-            width: 18.0,
-            height: 60.0
-        }) {
-            return mouse::Interaction::Grab;
-            // println!("clicked end");
-
+        for segment in &self.segments {
+            let start_portion = self.time_to_x(view_size, segment.start) - view_position.x;
+            let end_portion = self.time_to_x(view_size, segment.end) - view_position.x;
+
+            let mut handle_start_position = view_position.x;
+            handle_start_position += start_portion;
+
+            let mut handle_end_position = view_position.x;
+            handle_end_position += end_portion - 7.0;
+
+            if cursor.is_over(Rectangle {
+                x: handle_start_position - 11.0,
+                y: view_position.y,
+                width: 18.0,
+                height: 60.0
+            }) {
+                return mouse::Interaction::Grab;
+            } else if cursor.is_over(Rectangle {
+                x: handle_end_position - 11.0,
+                y: view_position.y,
+                width: 18.0,
+                height: 60.0
+            }) {
+                return mouse::Interaction::Grab;
+            }
         }
         mouse::Interaction::None
         // if  {
@@ -345,112 +500,93 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
 
     fn on_event(
             &mut self,
-            _state: &mut iced::advanced::widget::Tree,
+            tree: &mut iced::advanced::widget::Tree,
             event: Event,
             layout: layout::Layout<'_>,
             cursor: mouse::Cursor,
             _renderer: &Renderer,
             _clipboard: &mut dyn iced::advanced::Clipboard,
             shell: &mut iced::advanced::Shell<'_, Message>,
-            _viewport: &Rectangle,
+            viewport: &Rectangle,
         ) -> core::event::Status {
 
         match event {
-            Event::Keyboard(keyboard::Event::KeyPressed {
-                key,
-                modifiers,
-                text,
-                ..
-            }) => {
-                match key {
-                    Key::Named(named) => match named {
-                        Named::Space => {
-                            shell.publish((self.play_pause)());
-                            return core::event::Status::Captured;
-                        }
-                        Named::ArrowLeft => {
-                            if self.pressed_end {
-                                let mut new_position = self.end - (self.duration * 0.001);
-                                if new_position < 0.0 {
-                                    new_position = 0.0;
-                                }
-                                shell.publish((self.update_end)(new_position));
-                                return core::event::Status::Captured;
-                            }
-                            if self.pressed_start {
-                                let mut new_position = self.start - (self.duration * 0.001);
-                                if new_position < 0.0 {
-                                    new_position = 0.0;
-                                }
-                                // if new_position > self.duration {
-                                //     new_position = self.duration;
-                                // }
-                                shell.publish((self.update_start)(new_position));
-                                return core::event::Status::Captured;
-                            }
-                            let mut new_position = self.cursor_position - (self.duration * 0.001);
-                            if new_position < 0.0 {
-                                new_position = 0.0;
-                            }
-                            shell.publish((self.set_time)(new_position));
-                            return core::event::Status::Captured;
+            Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                let Some(action) = self.config.keys.action(&key, modifiers) else { return core::event::Status::Ignored };
+                match action {
+                    Action::PlayPause => shell.publish((self.play_pause)()),
+                    Action::StepBack => self.move_active(shell, self.step(self.active_position(), -1.0)),
+                    Action::StepForward => self.move_active(shell, self.step(self.active_position(), 1.0)),
+                    Action::PreviousKeyframe => {
+                        if let Some(keyframe) = self.previous_keyframe(self.active_position()) {
+                            self.move_active(shell, keyframe);
                         }
-                        Named::ArrowRight => {
-                            if self.pressed_end {
-                                let mut new_position = self.end + (self.duration * 0.001);
-                                if new_position > self.duration {
-                                    new_position = self.duration;
-                                }
-                                shell.publish((self.update_end)(new_position));
-                                return core::event::Status::Captured;
-                            }
-                            if self.pressed_start {
-                                let mut new_position = self.start + (self.duration * 0.001);
-                                if new_position > self.duration {
-                                    new_position = self.duration;
-                                }
-                                shell.publish((self.update_start)(new_position));
-                                return core::event::Status::Captured;
-                            }
-                            let mut new_position = self.cursor_position + (self.duration * 0.001);
-                            if new_position > self.duration {
-                                new_position = self.duration;
-                            }
-                            shell.publish((self.set_time)(new_position));
-                            return core::event::Status::Captured;
+                    }
+                    Action::NextKeyframe => {
+                        if let Some(keyframe) = self.next_keyframe(self.active_position()) {
+                            self.move_active(shell, keyframe);
                         }
-                        _ => core::event::Status::Ignored,
                     }
-                    Key::Character(char) => {
+                    Action::SetIn => shell.publish((self.set_in)()),
+                    Action::SetOut => shell.publish((self.set_out)()),
+                    Action::JumpToStart => shell.publish((self.set_time)(self.segments[self.selected].start)),
+                    Action::JumpToEnd => shell.publish((self.set_time)(self.segments[self.selected].end)),
+                    Action::ShuttleBack => shell.publish((self.shuttle)(-1)),
+                    Action::ShuttlePause => shell.publish((self.shuttle)(0)),
+                    Action::ShuttleForward => shell.publish((self.shuttle)(1)),
+                    Action::Slower => shell.publish((self.change_speed)(-1)),
+                    Action::Faster => shell.publish((self.change_speed)(1)),
+                    Action::ToggleLoop => shell.publish((self.toggle_loop)()),
+                    Action::Open => shell.publish((self.open)()),
+                    Action::AddSegment => shell.publish((self.add_segment)()),
+                    Action::RemoveSegment => shell.publish((self.remove_segment)()),
+                    Action::Export => shell.publish((self.export)()),
+                    Action::Restart => shell.publish((self.restart)()),
+                    Action::Cancel => shell.publish((self.cancel)()),
+                }
+                core::event::Status::Captured
+            }
 
-                        println!("char is {:?}", char);
-                        if char == "r" && modifiers.control() {
-                            println!("restart te thing!");
-                            shell.publish((self.restart)());
-                            return core::event::Status::Captured;
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                tree.state.downcast_mut::<State>().modifiers = modifiers;
+                core::event::Status::Ignored
+            }
 
-                        }
-                        core::event::Status::Ignored
-                    }
-                    _ => core::event::Status::Ignored
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let view_size = layout.bounds();
+                let Some(position) = cursor.position_over(view_size) else { return core::event::Status::Ignored };
+
+                let (x, y) = match delta {
+                    mouse::ScrollDelta::Lines { x, y } => (x, y),
+                    mouse::ScrollDelta::Pixels { x, y } => (x / 50.0, y / 50.0),
+                };
+                // the wheel zooms (with or without ctrl), shift or a sideways scroll moves the visible part instead
+                let (scroll, zoom) = if tree.state.downcast_ref::<State>().modifiers.shift() { (x + y, 0.0) } else { (x, y) };
+
+                let span = self.view_end - self.view_start;
+                if scroll != 0.0 {
+                    self.publish_view(shell, self.view_start - scroll * span * 0.1, span);
+                } else if zoom != 0.0 {
+                    self.zoom(shell, self.x_to_time(view_size, position.x), 0.8_f32.powf(zoom));
                 }
+                core::event::Status::Captured
             }
 
             Event::Mouse(mouse::Event::CursorMoved { .. }) => {
 
+                let segment = self.segments[self.selected];
                 if self.pressed_start {
                     let view_position = layout.position();
                     let view_size = layout.bounds();
 
                     let Some(position) = cursor.position() else { return core::event::Status::Ignored };
                     let mut x_position = position.x - view_position.x;
-                    if position.x > (view_size.x + (view_size.width / (self.duration / self.end)) - 20.0) {
-                        x_position = (view_size.width / (self.duration / self.end)) - 20.0;
+                    if position.x > self.time_to_x(view_size, segment.end) - 20.0 {
+                        x_position = self.time_to_x(view_size, segment.end) - 20.0 - view_position.x;
                     } else if position.x < view_position.x {
                         x_position = 0.0;
                     }
-                    let mut new_position = x_position / (view_size.width ) ;
-                    shell.publish((self.positional_update)(self.duration * new_position));
+                    shell.publish((self.positional_update)(self.x_to_time(view_size, view_position.x + x_position)));
 
                     return core::event::Status::Captured;
                 }
@@ -462,12 +598,11 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
                     let mut x_position = position.x - view_position.x;
                     if position.x > (view_size.width + view_position.x) {
                         x_position = view_size.width ;
-                    } else if position.x < (view_size.x + (view_size.width / (self.duration / self.start)) + 18.0) {
-                        x_position =  (view_size.width / (self.duration / self.start)) + 18.0;
+                    } else if position.x < self.time_to_x(view_size, segment.start) + 18.0 {
+                        x_position = self.time_to_x(view_size, segment.start) + 18.0 - view_position.x;
                     }
-                    let mut new_position = x_position / (view_size.width ) ;
 
-                    shell.publish((self.positional_update)(self.duration * new_position));
+                    shell.publish((self.positional_update)(self.x_to_time(view_size, view_position.x + x_position)));
                     return core::event::Status::Captured;
                 }
                 if self.pressed_anywhere {
@@ -483,9 +618,8 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
                         x_position = 0.0;
                     }
 
-                    let mut new_position = x_position / (view_size.width ) ;
 
-                    shell.publish((self.positional_update)(self.duration * new_position));
+                    shell.publish((self.positional_update)(self.x_to_time(view_size, view_position.x + x_position)));
                     return core::event::Status::Captured;
                 }
 
@@ -502,8 +636,7 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
                         x_position = 0.0;
                     }
 
-                    let mut new_position = x_position / (view_size.width ) ;
-                    shell.publish((self.mouse_move)(new_position));
+                    shell.publish((self.mouse_move)(self.x_to_time(view_size, view_position.x + x_position)));
                     return core::event::Status::Captured;
                 }
 
@@ -517,46 +650,53 @@ impl<Message, Theme, Renderer: iced::advanced::Renderer + iced::advanced::text::
                     let mut view_position = layout.position();
                     let view_size = layout.bounds();
 
-                    let start_portion = view_size.width / (self.duration / self.start);
-                    let end_portion = view_size.width / (self.duration / self.end);
-
-                    let mut handle_start_position = view_position.x;
-                    handle_start_position += start_portion;
-
-                    let mut handle_end_position = view_position.x;
-                    handle_end_position += end_portion - 7.0;
-
-                    if cursor.is_over(Rectangle {
-                        x: handle_start_position - 11.0,
-                        y: view_position.y,
-                        width: 18.0,
-                        height: 60.0
-                    }) {
-                        shell.publish((self.toggle_start)(true));
-                        return core::event::Status::Captured;
-                    } else if cursor.is_over(Rectangle {
-                        x: handle_end_position - 11.0,
-                        y: view_position.y,
-                        width: 18.0,
-                        height: 60.0
-                    }) {
-                        shell.publish((self.toggle_end)(true));
-                        return core::event::Status::Captured;
-
-                    } else {
-                        let Some(position) = cursor.position() else { return core::event::Status::Ignored };
-                        let mut x_position = position.x - view_position.x;
-                        if position.x > (view_size.width + view_position.x) {
-                            x_position = view_size.width ;
-                        } else if position.x < view_position.x {
-                            x_position = 0.0;
+                    for (index, segment) in self.segments.iter().enumerate() {
+                        let start_portion = self.time_to_x(view_size, segment.start) - view_position.x;
+                        let end_portion = self.time_to_x(view_size, segment.end) - view_position.x;
+
+                        let mut handle_start_position = view_position.x;
+                        handle_start_position += start_portion;
+
+                        let mut handle_end_position = view_position.x;
+                        handle_end_position += end_portion - 7.0;
+
+                        if cursor.is_over(Rectangle {
+                            x: handle_start_position - 11.0,
+                            y: view_position.y,
+                            width: 18.0,
+                            height: 60.0
+                        }) {
+                            shell.publish((self.select)(index));
+                            shell.publish((self.toggle_start)(true));
+                            return core::event::Status::Captured;
+                        } else if cursor.is_over(Rectangle {
+                            x: handle_end_position - 11.0,
+                            y: view_position.y,
+                            width: 18.0,
+                            height: 60.0
+                        }) {
+                            shell.publish((self.select)(index));
+                            shell.publish((self.toggle_end)(true));
+                            return core::event::Status::Captured;
                         }
+                    }
 
-                        let mut new_position = x_position / (view_size.width ) ;
-                        shell.publish((self.set_time)(self.duration * new_position));
-                        shell.publish((self.update_anywhere)(true));
-                        return core::event::Status::Captured;
+                    let Some(position) = cursor.position() else { return core::event::Status::Ignored };
+                    let mut x_position = position.x - view_position.x;
+                    if position.x > (view_size.width + view_position.x) {
+                        x_position = view_size.width ;
+                    } else if position.x < view_position.x {
+                        x_position = 0.0;
+                    }
+
+                    let time = self.x_to_time(view_size, view_position.x + x_position);
+                    // clicking inside of a segment also makes it the one being edited
+                    if let Some(index) = self.segments.iter().position(|segment| segment.start <= time && time <= segment.end) {
+                        shell.publish((self.select)(index));
                     }
+                    shell.publish((self.set_time)(time));
+                    shell.publish((self.update_anywhere)(true));
+                    return core::event::Status::Captured;
                 }
                 core::event::Status::Ignored
             },